@@ -32,6 +32,12 @@ fn benchmark_buffer(c: &mut Criterion) {
                 config.sinks["out"].buffer = BufferConfig::Memory {
                     max_events: 100,
                     when_full: Default::default(),
+                    channel_kind: Default::default(),
+                    in_memory_compression: false,
+                    overflow_max_size: None,
+                    block_timeout_secs: None,
+                    max_size: None,
+                    ttl_from_field: None,
                 };
 
                 let rt = runtime();
@@ -80,6 +86,14 @@ fn benchmark_buffer(c: &mut Criterion) {
                 config.sinks["out"].buffer = BufferConfig::Disk {
                     max_size: 1_000_000,
                     when_full: Default::default(),
+                    require_free_space: false,
+                    reader_memory_budget: None,
+                    min_free_space: None,
+                    block_timeout_secs: None,
+                    open_retry: None,
+                    ack_policy: Default::default(),
+                    growth_alert_secs: None,
+                    ttl_from_field: None,
                 };
                 config.global.data_dir = Some(data_dir.path().to_path_buf());
                 let rt = runtime();
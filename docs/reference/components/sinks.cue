@@ -66,6 +66,61 @@ components: sinks: [Name=string]: {
 				type: object: {
 					examples: []
 					options: {
+						ack_policy: {
+							common:        false
+							description:   "When the disk buffer may delete the events it has sent to the sink."
+							required:      false
+							relevant_when: "type = \"disk\""
+							type: string: {
+								default: "on_confirm"
+								enum: {
+									on_confirm: "Once the sink has delivered them. Events the sink has not delivered yet are sent again after a restart."
+									on_read:    "As soon as they are sent to the sink, freeing disk space sooner. Events the sink has not delivered yet are lost when Vector stops. This should be used for fire-and-forget sinks."
+								}
+								syntax: "literal"
+							}
+						}
+						block_timeout_secs: {
+							common:      false
+							description: "The longest time to apply back pressure for when `when_full` is `block`. Once an input has been blocked for this long, it drops new events until the buffer has room again, and is allowed to block for this long again after that. By default, back pressure is applied for as long as the buffer is full."
+							required:    false
+							type: uint: {
+								default: null
+								unit:    "seconds"
+							}
+						}
+						channel_kind: {
+							common:        false
+							description:   "The channel backing the in-memory buffer."
+							required:      false
+							relevant_when: "type = \"memory\""
+							type: string: {
+								default: "bounded"
+								enum: {
+									bounded:                 "Holds up to `max_events` events and applies the `when_full` behavior once full."
+									rendezvous:              "Holds no shared capacity, events are handed off directly to the sink. Each input may still have a single event waiting for handoff."
+									unbounded_with_soft_cap: "Never applies back pressure, but drops new events once `max_events` events are queued, whatever `when_full` is. Not supported with `block_timeout_secs`."
+								}
+								syntax: "literal"
+							}
+						}
+						growth_alert_secs: {
+							common:        false
+							description:   "Logs a warning and increments the `disk_buffer_growth_alerts_total` metric once the disk buffer has only been growing for this long, which usually means its sink is stuck. The buffer's size is checked every 5 seconds. By default, no alert is raised."
+							required:      false
+							relevant_when: "type = \"disk\""
+							type: uint: {
+								default: null
+								unit:    "seconds"
+							}
+						}
+						in_memory_compression: {
+							common:        false
+							description:   "Whether to hold events compressed in memory, trading CPU for a smaller memory footprint. Compressed events go through their encoded form, so their finalizers are dropped. Not supported with the `unbounded_with_soft_cap` channel kind."
+							required:      false
+							relevant_when: "type = \"memory\""
+							type: bool: default: false
+						}
 						max_events: {
 							common:        true
 							description:   "The maximum number of [events][docs.data-model] allowed in the buffer."
@@ -77,14 +132,121 @@ components: sinks: [Name=string]: {
 							}
 						}
 						max_size: {
-							description:   "The maximum size of the buffer on the disk."
-							required:      true
-							relevant_when: "type = \"disk\""
+							common:      true
+							description: "The maximum size of the buffer on the disk, required for disk buffers. A disk buffer drops events too large to ever fit, larger than about three fifths of `max_size`, whatever `when_full` is, and counts them as dropped. Memory buffers may set it too, to cap the approximate size of the events they hold, in which case they are full once either `max_events` or `max_size` is reached. With `in_memory_compression`, events count by their compressed size. Not supported with the `unbounded_with_soft_cap` channel, or the `drop_oldest` and `overflow` policies."
+							required:    false
 							type: uint: {
 								examples: [104900000]
 								unit: "bytes"
 							}
 						}
+						min_free_space: {
+							common:        false
+							description:   "The minimum free space to leave on the filesystem holding the `data_dir`. While less space is free, the buffer refuses new events as if it were full, applying `when_full`. Free space is checked every 5 seconds."
+							required:      false
+							relevant_when: "type = \"disk\""
+							type: uint: {
+								default: null
+								unit:    "bytes"
+							}
+						}
+						open_retry: {
+							common:        false
+							description:   "Retries opening the disk buffer when it fails for a reason that may pass, such as the `data_dir` not being mounted yet. By default, Vector fails to start right away."
+							required:      false
+							relevant_when: "type = \"disk\""
+							type: object: {
+								examples: []
+								options: {
+									attempts: {
+										common:      true
+										description: "The number of attempts to make, including the first one."
+										required:    true
+										type: uint: {
+											examples: [5]
+											unit: null
+										}
+									}
+									backoff_secs: {
+										common:      false
+										description: "How long to wait between attempts."
+										required:    false
+										type: uint: {
+											default: 1
+											unit:    "seconds"
+										}
+									}
+								}
+							}
+						}
+						overflow_max_size: {
+							common:        false
+							description:   "The maximum size of the disk buffer that a memory buffer spills to when `when_full` is `overflow`. Required for that policy."
+							required:      false
+							relevant_when: "type = \"memory\""
+							type: uint: {
+								default: null
+								unit:    "bytes"
+							}
+						}
+						reader_memory_budget: {
+							common:        false
+							description:   "The maximum size of the records the buffer's reader holds in memory at once. By default the reader reads ahead up to 100 records regardless of their size."
+							required:      false
+							relevant_when: "type = \"disk\""
+							type: uint: {
+								default: null
+								unit:    "bytes"
+							}
+						}
+						require_free_space: {
+							common:        false
+							description:   "Whether to refuse to start when the filesystem holding the `data_dir` does not have enough free space for the buffer to reach `max_size`. When disabled, a warning is logged instead."
+							required:      false
+							relevant_when: "type = \"disk\""
+							type: bool: default: false
+						}
+						ttl_from_field: {
+							common:      false
+							description: "Drops events as they are read from the buffer once the timestamp in one of their fields is older than a TTL, however long they were buffered. Events dropped from a disk buffer are still acked in order."
+							required:    false
+							type: object: {
+								examples: []
+								options: {
+									field: {
+										common:      true
+										description: "The field holding the event's timestamp, either a timestamp or an RFC 3339 string."
+										required:    true
+										type: string: {
+											examples: ["timestamp"]
+											syntax: "literal"
+										}
+									}
+									on_invalid: {
+										common:      false
+										description: "What to do with events whose field is missing or does not hold a valid timestamp, including metric events."
+										required:    false
+										type: string: {
+											default: "keep"
+											enum: {
+												keep: "Keeps the event."
+												drop: "Drops the event."
+											}
+											syntax: "literal"
+										}
+									}
+									ttl_secs: {
+										common:      true
+										description: "How long after its timestamp an event expires."
+										required:    true
+										type: uint: {
+											examples: [3600]
+											unit: "seconds"
+										}
+									}
+								}
+							}
+						}
 						type: {
 							common:      true
 							description: "The buffer's type and storage mechanism."
@@ -105,8 +267,10 @@ components: sinks: [Name=string]: {
 							type: string: {
 								default: "block"
 								enum: {
-									block:       "Applies back pressure when the buffer is full. This prevents data loss, but will cause data to pile up on the edge."
+									block:       "Applies back pressure when the buffer is full. This prevents data loss, but will cause data to pile up on the edge. The `unbounded_with_soft_cap` channel kind drops new data instead."
 									drop_newest: "Drops new data as it's received. This data is lost. This should be used when performance is the highest priority."
+									drop_oldest: "Drops the oldest queued data to make room for new data as it's received. This data is lost. This should be used when recent data matters most. Only supported for bounded, uncompressed memory buffers."
+									overflow:    "Spills new data to a disk buffer in the `data_dir` while the memory buffer is full, applying back pressure once that is full too. Spilled data is sent after any data that arrived in memory meanwhile. Only supported for memory buffers."
 								}
 								syntax: "literal"
 							}
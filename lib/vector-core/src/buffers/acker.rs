@@ -19,13 +19,7 @@ impl Acker {
     pub fn ack(&self, num: usize) {
         // Only ack items if the amount to ack is larger than zero.
         if num > 0 {
-            match self {
-                Acker::Null => {}
-                Acker::Disk(counter, notifier) => {
-                    counter.fetch_add(num, Ordering::Relaxed);
-                    notifier.wake();
-                }
-            }
+            self.forward(num);
 
             // WARN this string "events_out_total" is a duplicate of the metric
             // name in `ROOT/src/internal_events/topology.rs`. `Acker` had a
@@ -39,6 +33,20 @@ impl Acker {
         }
     }
 
+    /// Pass `num` acks on to the buffer without counting them as sent
+    /// events, for acks that another acker has already counted.
+    pub(crate) fn forward(&self, num: usize) {
+        if num > 0 {
+            match self {
+                Acker::Null => {}
+                Acker::Disk(counter, notifier) => {
+                    counter.fetch_add(num, Ordering::Relaxed);
+                    notifier.wake();
+                }
+            }
+        }
+    }
+
     pub fn new_for_testing() -> (Self, Arc<AtomicUsize>) {
         let ack_counter = Arc::new(AtomicUsize::new(0));
         let notifier = Arc::new(AtomicWaker::new());
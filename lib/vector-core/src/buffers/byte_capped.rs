@@ -0,0 +1,195 @@
+use crate::event::{proto, Event};
+use futures::{channel::mpsc, Sink, Stream};
+use pin_project::pin_project;
+use prost::Message;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// Create a channel holding up to `max_events` events, and no more events
+/// once their estimated size reaches `max_size` bytes.
+///
+/// Sizes are estimated from the events' protobuf encoding. Senders wait for
+/// room once `max_size` is reached, so each sender may overshoot it by the
+/// size of one event.
+pub fn channel(max_events: usize, max_size: usize) -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel(max_events);
+    let room = Arc::new(Room::new(max_size));
+
+    let tx = Sender {
+        inner: tx,
+        room: Arc::clone(&room),
+    };
+    let rx = Receiver { inner: rx, room };
+    (tx, rx)
+}
+
+/// The bytes queued in a channel, and the senders waiting for them to drop
+/// below `max_size`.
+pub(super) struct Room {
+    queued_bytes: AtomicUsize,
+    senders: Mutex<Vec<Waker>>,
+    max_size: usize,
+}
+
+impl Room {
+    pub(super) fn new(max_size: usize) -> Self {
+        Self {
+            queued_bytes: AtomicUsize::new(0),
+            senders: Mutex::new(Vec::new()),
+            max_size,
+        }
+    }
+
+    pub(super) fn queued_bytes(&self) -> usize {
+        self.queued_bytes.load(Ordering::Relaxed)
+    }
+
+    /// Ready while less than `max_size` is queued, otherwise `cx` is woken
+    /// once the receiver takes something.
+    pub(super) fn poll_ready(&self, cx: &mut Context<'_>) -> Poll<()> {
+        if self.queued_bytes() < self.max_size {
+            return Poll::Ready(());
+        }
+        let mut senders = self.senders.lock().unwrap();
+        if !senders.iter().any(|waker| waker.will_wake(cx.waker())) {
+            senders.push(cx.waker().clone());
+        }
+        // Check again, in case the receiver made room before the waker was
+        // registered.
+        if self.queued_bytes() < self.max_size {
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+
+    /// Account for `size` bytes sent. Senders do so before sending, so the
+    /// receiver can never subtract first.
+    pub(super) fn add(&self, size: usize) {
+        self.queued_bytes.fetch_add(size, Ordering::Relaxed);
+    }
+
+    /// Account for `size` bytes received or failed to send, waking the
+    /// senders waiting for room.
+    pub(super) fn remove(&self, size: usize) {
+        self.queued_bytes.fetch_sub(size, Ordering::Relaxed);
+        for waker in self.senders.lock().unwrap().drain(..) {
+            waker.wake();
+        }
+    }
+}
+
+#[pin_project]
+#[derive(Clone)]
+pub struct Sender {
+    #[pin]
+    inner: mpsc::Sender<(usize, Event)>,
+    room: Arc<Room>,
+}
+
+impl Sender {
+    /// The estimated size of all events currently queued in the channel.
+    pub fn queued_bytes(&self) -> usize {
+        self.room.queued_bytes()
+    }
+}
+
+impl Sink<Event> for Sender {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        futures::ready!(this.inner.poll_ready(cx))
+            .map_err(|error| error!(message = "Sender error.", %error))?;
+        this.room.poll_ready(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Event) -> Result<(), Self::Error> {
+        let this = self.project();
+        let size = proto::EventWrapper::from(item.clone()).encoded_len();
+
+        this.room.add(size);
+        this.inner.start_send((size, item)).map_err(|error| {
+            this.room.remove(size);
+            error!(message = "Sender error.", %error);
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project()
+            .inner
+            .poll_flush(cx)
+            .map_err(|error| error!(message = "Sender error.", %error))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project()
+            .inner
+            .poll_close(cx)
+            .map_err(|error| error!(message = "Sender error.", %error))
+    }
+}
+
+#[pin_project]
+pub struct Receiver {
+    #[pin]
+    inner: mpsc::Receiver<(usize, Event)>,
+    room: Arc<Room>,
+}
+
+impl Stream for Receiver {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        this.inner.poll_next(cx).map(|item| {
+            item.map(|(size, event)| {
+                // Subtracting the size added for this very event keeps the
+                // total from drifting.
+                this.room.remove(size);
+                event
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::channel;
+    use crate::event::Event;
+    use futures::{future, Sink, Stream};
+    use std::task::Poll;
+
+    #[tokio::test]
+    async fn blocks_at_max_size_before_max_events() {
+        future::lazy(|cx| {
+            let (tx, rx) = channel(100, 1000);
+            let event = Event::from("x".repeat(400));
+
+            let mut tx = Box::pin(tx);
+            for _ in 0..3 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(tx.as_mut().start_send(event.clone()), Ok(()));
+            }
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+            assert!(tx.queued_bytes() >= 1000);
+
+            // Draining makes room again.
+            let mut rx = Box::pin(rx);
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(event.clone())));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+
+            for _ in 0..2 {
+                assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(event.clone())));
+            }
+            assert_eq!(tx.queued_bytes(), 0);
+        })
+        .await;
+    }
+}
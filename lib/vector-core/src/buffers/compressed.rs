@@ -0,0 +1,180 @@
+use super::byte_capped::Room;
+use crate::event::{proto, Event};
+use bytes::Bytes;
+use futures::{channel::mpsc, Sink, Stream};
+use pin_project::pin_project;
+use prost::Message;
+use std::{
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// Create a channel holding up to `max_events` events in snappy-compressed
+/// form, trading CPU for memory. Events are decompressed as they are read.
+///
+/// With a `max_size`, the channel also holds no more events once their
+/// compressed size reaches it, each sender overshooting it by at most one
+/// event.
+///
+/// As with the disk buffer, events go through their protobuf encoding, so
+/// their finalizers are not carried across.
+pub fn channel(max_events: usize, max_size: Option<usize>) -> (Sender, Receiver) {
+    let (tx, rx) = mpsc::channel(max_events);
+    let room = Arc::new(Room::new(max_size.unwrap_or(usize::MAX)));
+
+    let tx = Sender {
+        inner: tx,
+        room: Arc::clone(&room),
+    };
+    let rx = Receiver { inner: rx, room };
+    (tx, rx)
+}
+
+#[pin_project]
+#[derive(Clone)]
+pub struct Sender {
+    #[pin]
+    inner: mpsc::Sender<Bytes>,
+    room: Arc<Room>,
+}
+
+impl Sender {
+    /// The compressed size of all events currently queued in the channel.
+    pub fn queued_bytes(&self) -> usize {
+        self.room.queued_bytes()
+    }
+}
+
+impl Sink<Event> for Sender {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        futures::ready!(this.inner.poll_ready(cx))
+            .map_err(|error| error!(message = "Sender error.", %error))?;
+        this.room.poll_ready(cx).map(Ok)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Event) -> Result<(), Self::Error> {
+        let this = self.project();
+        let value = compress(item);
+        let size = value.len();
+
+        this.room.add(size);
+        this.inner.start_send(value).map_err(|error| {
+            this.room.remove(size);
+            error!(message = "Sender error.", %error);
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project()
+            .inner
+            .poll_flush(cx)
+            .map_err(|error| error!(message = "Sender error.", %error))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project()
+            .inner
+            .poll_close(cx)
+            .map_err(|error| error!(message = "Sender error.", %error))
+    }
+}
+
+#[pin_project]
+pub struct Receiver {
+    #[pin]
+    inner: mpsc::Receiver<Bytes>,
+    room: Arc<Room>,
+}
+
+impl Stream for Receiver {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        this.inner.poll_next(cx).map(|value| {
+            value.map(|value| {
+                this.room.remove(value.len());
+                decompress(&value)
+            })
+        })
+    }
+}
+
+fn compress(event: Event) -> Bytes {
+    let mut value = vec![];
+    proto::EventWrapper::from(event).encode(&mut value).unwrap(); // This will not error when writing to a Vec
+    snap::raw::Encoder::new()
+        .compress_vec(&value)
+        .expect("Event too large to compress")
+        .into()
+}
+
+fn decompress(value: &[u8]) -> Event {
+    // Everything in the channel was compressed by `Sender`, so neither step
+    // can fail short of memory corruption.
+    let value = snap::raw::Decoder::new()
+        .decompress_vec(value)
+        .expect("Event compressed by Sender");
+    proto::EventWrapper::decode(value.as_slice())
+        .expect("Event encoded by Sender")
+        .into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::channel;
+    use crate::event::{proto, Event};
+    use futures::{future, Sink, Stream};
+    use prost::Message;
+    use std::task::Poll;
+
+    #[tokio::test]
+    async fn round_trips_compressed() {
+        future::lazy(|cx| {
+            let (tx, rx) = channel(2, None);
+            let event = Event::from("compressible ".repeat(100));
+
+            let mut encoded = vec![];
+            proto::EventWrapper::from(event.clone())
+                .encode(&mut encoded)
+                .unwrap();
+
+            let mut tx = Box::pin(tx);
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(event.clone()), Ok(()));
+
+            let queued_bytes = tx.queued_bytes();
+            assert!(queued_bytes > 0);
+            assert!(queued_bytes < encoded.len());
+
+            let mut rx = Box::pin(rx);
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(event)));
+            assert_eq!(tx.queued_bytes(), 0);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn blocks_at_compressed_max_size() {
+        future::lazy(|cx| {
+            let event = Event::from("compressible ".repeat(100));
+            let (tx, rx) = channel(100, Some(1));
+
+            let mut tx = Box::pin(tx);
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(event.clone()), Ok(()));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+
+            // Draining makes room again.
+            let mut rx = Box::pin(rx);
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(event)));
+            assert_eq!(tx.queued_bytes(), 0);
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+        })
+        .await;
+    }
+}
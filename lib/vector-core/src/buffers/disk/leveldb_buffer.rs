@@ -17,18 +17,32 @@ use std::{
     path::{Path, PathBuf},
     pin::Pin,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc, Mutex,
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, Mutex, Weak,
     },
     task::{Context, Poll, Waker},
 };
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
-use super::{DataDirOpenError, Error};
+use super::{DataDirOpenError, Error, ImportWriteError, InvalidStream, StreamError};
 use crate::buffers::Acker;
 
 /// How much of disk buffer needs to be deleted before we trigger compaction.
 const MAX_UNCOMPACTED_DENOMINATOR: usize = 10;
 
+/// Identifies a stream of disk buffer records, followed by the version of
+/// the stream format.
+const STREAM_MAGIC: &[u8; 8] = b"VECDBUF\0";
+const STREAM_VERSION: u8 = 1;
+/// Each record in a stream is preceded by this tag, its key and its length.
+const STREAM_RECORD: u8 = 1;
+/// Marks the end of a stream, so a truncated stream is not mistaken for a
+/// complete one.
+const STREAM_END: u8 = 0;
+/// How many records are read from or written to the database at once while
+/// streaming.
+const STREAM_BATCH_SIZE: usize = 100;
+
 #[derive(Copy, Clone, Debug)]
 struct Key(pub usize);
 
@@ -55,6 +69,9 @@ pub struct Writer {
     max_size: usize,
     current_size: Arc<AtomicUsize>,
     slot: Option<Event>,
+    drop_when_full: bool,
+    dropped_events: Option<Arc<AtomicU64>>,
+    low_space: Arc<AtomicBool>,
 }
 
 // Writebatch isn't Send, but the leveldb docs explicitly say that it's okay to share across threads
@@ -72,6 +89,9 @@ impl Clone for Writer {
             max_size: self.max_size,
             current_size: Arc::clone(&self.current_size),
             slot: None,
+            drop_when_full: self.drop_when_full,
+            dropped_events: self.dropped_events.clone(),
+            low_space: Arc::clone(&self.low_space),
         }
     }
 }
@@ -92,7 +112,17 @@ impl Sink<Event> for Writer {
     fn start_send(mut self: Pin<&mut Self>, item: Event) -> Result<(), Self::Error> {
         if let Some(event) = self.try_send(item) {
             debug_assert!(self.slot.is_none());
-            self.slot = Some(event);
+            if self.drop_when_full {
+                debug!(
+                    message = "Shedding load; dropping event.",
+                    internal_log_rate_secs = 10
+                );
+                if let Some(dropped_events) = &self.dropped_events {
+                    dropped_events.fetch_add(1, Ordering::Relaxed);
+                }
+            } else {
+                self.slot = Some(event);
+            }
         }
         Ok(())
     }
@@ -132,11 +162,55 @@ impl Sink<Event> for Writer {
 }
 
 impl Writer {
+    /// Drop events which do not fit into the buffer rather than holding on
+    /// to them until enough space is freed.
+    pub fn set_drop_when_full(&mut self, drop_when_full: bool) {
+        self.drop_when_full = drop_when_full;
+    }
+
+    /// Increment `dropped_events` for every event dropped because it did not
+    /// fit into the buffer.
+    pub fn set_dropped_events(&mut self, dropped_events: Arc<AtomicU64>) {
+        self.dropped_events = Some(dropped_events);
+    }
+
+    /// A handle to refuse events, as if the buffer were full, while the
+    /// disk is low on space.
+    pub fn low_space(&self) -> LowSpace {
+        LowSpace {
+            low_space: Arc::downgrade(&self.low_space),
+            blocked_write_tasks: Arc::clone(&self.blocked_write_tasks),
+        }
+    }
+
+    pub fn current_size(&self) -> Arc<AtomicUsize> {
+        Arc::clone(&self.current_size)
+    }
+
     fn try_send(&mut self, event: Event) -> Option<Event> {
-        let mut value = vec![];
-        proto::EventWrapper::from(event).encode(&mut value).unwrap(); // This will not error when writing to a Vec
+        if self.low_space.load(Ordering::Relaxed) {
+            self.flush();
+            return Some(event);
+        }
+
+        let value = encode(event);
         let event_size = value.len();
 
+        if event_size + event_size / 2 > self.max_size {
+            // Even an empty buffer never has room for one and a half times
+            // this event's size, holding on to it would block the writer
+            // forever, whatever the drop policy.
+            error!(
+                message = "Event is larger than the disk buffer; dropping event.",
+                %event_size,
+                internal_log_rate_secs = 10
+            );
+            if let Some(dropped_events) = &self.dropped_events {
+                dropped_events.fetch_add(1, Ordering::Relaxed);
+            }
+            return None;
+        }
+
         if self.current_size.fetch_add(event_size, Ordering::Relaxed) + (event_size / 2)
             > self.max_size
         {
@@ -189,8 +263,10 @@ impl Drop for Writer {
             // or we are unwinding the stack.
             //
             // We can't be picky at the moment so we will allow
-            // for the buffer to exceed configured limit.
+            // for the buffer to exceed configured limit, or to write
+            // while the disk is low on space.
             self.max_size = usize::MAX;
+            self.low_space = Arc::new(AtomicBool::new(false));
             assert!(self.try_send(event).is_none());
         }
 
@@ -205,6 +281,34 @@ impl Drop for Writer {
     }
 }
 
+/// Marks a buffer as low on disk space. The buffer's writers refuse events
+/// while it is set, applying the drop policy as when the buffer is full.
+#[derive(Clone)]
+pub struct LowSpace {
+    low_space: Weak<AtomicBool>,
+    blocked_write_tasks: Arc<Mutex<Vec<Waker>>>,
+}
+
+impl LowSpace {
+    /// Set whether the disk is low on space, waking any blocked writers
+    /// once it no longer is.
+    ///
+    /// Returns `false` once every writer of the buffer has been dropped.
+    pub fn set(&self, low_space: bool) -> bool {
+        match self.low_space.upgrade() {
+            Some(flag) => {
+                if flag.swap(low_space, Ordering::Relaxed) && !low_space {
+                    for task in self.blocked_write_tasks.lock().unwrap().drain(..) {
+                        task.wake();
+                    }
+                }
+                true
+            }
+            None => false,
+        }
+    }
+}
+
 pub struct Reader {
     db: Arc<Database<Key>>,
     read_offset: usize,
@@ -217,6 +321,7 @@ pub struct Reader {
     unacked_sizes: VecDeque<usize>,
     buffer: Vec<Vec<u8>>,
     max_uncompacted_size: usize,
+    memory_budget: Option<usize>,
 }
 
 // Writebatch isn't Send, but the leveldb docs explicitly say that it's okay to share across threads
@@ -233,6 +338,10 @@ impl Stream for Reader {
         self.delete_acked();
 
         if self.buffer.is_empty() {
+            // Always read at least one record, however large, so the reader
+            // can make progress.
+            let memory_budget = self.memory_budget.unwrap_or(usize::MAX);
+            let mut batch_size = 0;
             // This will usually complete instantly, but in the case of a large queue (or a fresh launch of
             // the app), this will have to go to disk.
             let new_data = tokio::task::block_in_place(|| {
@@ -240,6 +349,11 @@ impl Stream for Reader {
                     .value_iter(ReadOptions::new())
                     .from(&Key(self.read_offset))
                     .to(&Key(self.read_offset + 100))
+                    .take_while(|value| {
+                        let first = batch_size == 0;
+                        batch_size += value.len();
+                        first || batch_size <= memory_budget
+                    })
                     .collect()
             });
             self.buffer = new_data;
@@ -326,6 +440,51 @@ impl Reader {
 
 pub struct Buffer;
 
+/// Encode `event` into the value of its record.
+fn encode(event: Event) -> Vec<u8> {
+    let mut value = vec![];
+    proto::EventWrapper::from(event).encode(&mut value).unwrap(); // This will not error when writing to a Vec
+    value
+}
+
+/// The number of bytes `event` takes up in the buffer, as counted against
+/// `max_size`.
+pub(super) fn encoded_size(event: &Event) -> usize {
+    encode(event.clone()).len()
+}
+
+/// The smallest `max_size` with which the buffer can hold an empty log
+/// event, the smallest record there is.
+pub(super) fn min_max_size() -> usize {
+    let record_size = encoded_size(&Event::new_empty_log());
+    // The writer only admits an event while there is room for one and a half
+    // times its size, in what is left of `max_size` after the part kept for
+    // compaction.
+    let required = record_size + record_size / 2;
+    let denominator = MAX_UNCOMPACTED_DENOMINATOR;
+    (required * denominator + denominator - 2) / (denominator - 1)
+}
+
+impl Buffer {
+    /// Build a new disk buffer like [`super::DiskBuffer::build`], bounding
+    /// the records the reader holds in memory at once to `memory_budget`
+    /// bytes.
+    ///
+    /// # Errors
+    ///
+    /// Function will fail if the permissions of `path` are not correct, if
+    /// there is no space available on disk etc.
+    pub fn build_with_memory_budget(
+        path: PathBuf,
+        max_size: usize,
+        memory_budget: Option<usize>,
+    ) -> Result<(Writer, Reader, Acker), Error> {
+        let (writer, mut reader, acker) = <Self as super::DiskBuffer>::build(path, max_size)?;
+        reader.memory_budget = memory_budget;
+        Ok((writer, reader, acker))
+    }
+}
+
 /// Read the byte size of the database
 ///
 /// There is a mismatch between leveldb's mechanism and vector's. While vector
@@ -351,6 +510,176 @@ fn db_initial_size(path: &Path) -> Result<usize, Error> {
     Ok(db.value_iter(ReadOptions::new()).map(|v| v.len()).sum())
 }
 
+/// Write every record of the disk buffer at `path` to `writer`, returning the
+/// number of records written. See [`super::stream_out`].
+pub(super) async fn stream_out<W: AsyncWrite + Unpin>(
+    path: &Path,
+    mut writer: W,
+) -> Result<usize, Error> {
+    let db: Arc<Database<Key>> = Arc::new(Database::open(path, Options::new()).with_context(
+        || DataDirOpenError {
+            data_dir: path.parent().expect("always a parent"),
+        },
+    )?);
+
+    writer.write_all(STREAM_MAGIC).await.context(StreamError)?;
+    writer.write_u8(STREAM_VERSION).await.context(StreamError)?;
+
+    let mut count = 0;
+    let mut next = 0;
+    loop {
+        // Only hold the database iterator while reading a batch, it must
+        // not be kept across an await.
+        let db = Arc::clone(&db);
+        let batch: Vec<(Key, Vec<u8>)> = tokio::task::spawn_blocking(move || {
+            db.iter(ReadOptions::new())
+                .from(&Key(next))
+                .take(STREAM_BATCH_SIZE)
+                .collect()
+        })
+        .await
+        .expect("Reading a batch of disk buffer records panicked");
+        let last = match batch.last() {
+            Some((key, _)) => key.0,
+            None => break,
+        };
+
+        for (key, value) in &batch {
+            writer.write_u8(STREAM_RECORD).await.context(StreamError)?;
+            writer.write_u64(key.0 as u64).await.context(StreamError)?;
+            writer
+                .write_u64(value.len() as u64)
+                .await
+                .context(StreamError)?;
+            writer.write_all(value).await.context(StreamError)?;
+        }
+        count += batch.len();
+        next = last + 1;
+    }
+
+    writer.write_u8(STREAM_END).await.context(StreamError)?;
+    writer.flush().await.context(StreamError)?;
+    Ok(count)
+}
+
+/// Read records written by [`stream_out`] from `reader` into a new disk
+/// buffer at `path`, returning the number of records read. See
+/// [`super::import_stream`].
+pub(super) async fn import_stream<R: AsyncRead + Unpin>(
+    mut reader: R,
+    path: &Path,
+) -> Result<usize, Error> {
+    let mut magic = [0; 8];
+    reader.read_exact(&mut magic).await.context(StreamError)?;
+    if magic != *STREAM_MAGIC {
+        return InvalidStream {
+            reason: "not a disk buffer stream",
+        }
+        .fail();
+    }
+    if reader.read_u8().await.context(StreamError)? != STREAM_VERSION {
+        return InvalidStream {
+            reason: "unsupported stream version",
+        }
+        .fail();
+    }
+
+    let db_path = path.to_path_buf();
+    let db: Database<Key> = tokio::task::spawn_blocking(move || {
+        let mut options = Options::new();
+        options.create_if_missing = true;
+        options.error_if_exists = true;
+        Database::open(&db_path, options)
+    })
+    .await
+    .expect("Opening the imported disk buffer panicked")
+    .with_context(|| DataDirOpenError {
+        data_dir: path.parent().expect("always a parent"),
+    })?;
+    let db = Arc::new(db);
+
+    match import_records(reader, &db).await {
+        Ok(count) => Ok(count),
+        Err(error) => {
+            // Don't leave a partial buffer behind, it would both be read as
+            // a complete one and make importing again fail.
+            drop(db);
+            if let Err(error) = tokio::fs::remove_dir_all(path).await {
+                error!(message = "Failed to remove partially imported disk buffer.", %error, ?path);
+            }
+            Err(error)
+        }
+    }
+}
+
+async fn import_records<R: AsyncRead + Unpin>(
+    mut reader: R,
+    db: &Arc<Database<Key>>,
+) -> Result<usize, Error> {
+    let mut count = 0;
+    let mut batch = Vec::with_capacity(STREAM_BATCH_SIZE);
+    loop {
+        let end = match reader.read_u8().await.context(StreamError)? {
+            STREAM_RECORD => {
+                let key = read_usize(&mut reader).await?;
+                let len = read_usize(&mut reader).await?;
+                // The length is untrusted, so grow the value as it is read
+                // rather than allocating it all up front.
+                let mut value = Vec::new();
+                (&mut reader)
+                    .take(len as u64)
+                    .read_to_end(&mut value)
+                    .await
+                    .context(StreamError)?;
+                if value.len() < len {
+                    return InvalidStream {
+                        reason: "truncated record",
+                    }
+                    .fail();
+                }
+                batch.push((Key(key), value));
+                false
+            }
+            STREAM_END => true,
+            _ => {
+                return InvalidStream {
+                    reason: "unknown frame tag",
+                }
+                .fail()
+            }
+        };
+
+        if end || batch.len() >= STREAM_BATCH_SIZE {
+            let db = Arc::clone(db);
+            let records = std::mem::replace(&mut batch, Vec::with_capacity(STREAM_BATCH_SIZE));
+            count += records.len();
+            tokio::task::spawn_blocking(move || {
+                // `Writebatch` isn't `Send`, so it is built on the blocking
+                // thread that writes it.
+                let mut writebatch = Writebatch::new();
+                for (key, value) in &records {
+                    writebatch.put(*key, value);
+                }
+                db.write(WriteOptions::new(), &writebatch)
+            })
+            .await
+            .expect("Writing a batch of imported records panicked")
+            .context(ImportWriteError)?;
+        }
+
+        if end {
+            return Ok(count);
+        }
+    }
+}
+
+async fn read_usize<R: AsyncRead + Unpin>(reader: &mut R) -> Result<usize, Error> {
+    let value = reader.read_u64().await.context(StreamError)?;
+    value.try_into().map_err(|_| Error::InvalidStream {
+        reason: "record does not fit in memory",
+    })
+}
+
 impl super::DiskBuffer for Buffer {
     type Writer = Writer;
     type Reader = Reader;
@@ -402,6 +731,9 @@ impl super::DiskBuffer for Buffer {
             max_size,
             current_size: Arc::clone(&current_size),
             slot: None,
+            drop_when_full: false,
+            dropped_events: None,
+            low_space: Arc::new(AtomicBool::new(false)),
         };
 
         let mut reader = Reader {
@@ -416,6 +748,7 @@ impl super::DiskBuffer for Buffer {
             uncompacted_size: 1,
             unacked_sizes: VecDeque::new(),
             buffer: Vec::new(),
+            memory_budget: None,
         };
         // Compact on every start
         reader.compact();
@@ -423,3 +756,126 @@ impl super::DiskBuffer for Buffer {
         Ok((writer, reader, acker))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{Buffer, Key, STREAM_MAGIC, STREAM_RECORD, STREAM_VERSION};
+    use crate::{
+        buffers::disk::{estimate_size, import_stream, stream_out},
+        event::Event,
+    };
+    use futures::{SinkExt, StreamExt};
+    use leveldb::database::{
+        iterator::Iterable,
+        options::{Options, ReadOptions},
+        Database,
+    };
+    use std::path::Path;
+    use tempfile::tempdir;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn read_batch_stays_within_memory_budget() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("buffer");
+        let (mut writer, mut reader, _acker) =
+            Buffer::build_with_memory_budget(path, 1_000_000, Some(3_000)).unwrap();
+
+        for _ in 0..10 {
+            writer.send(Event::from("x".repeat(1000))).await.unwrap();
+        }
+
+        for _ in 0..10 {
+            assert!(reader.next().await.is_some());
+            let buffered = reader.buffer.iter().map(Vec::len).sum::<usize>();
+            assert!(buffered <= 3_000, "{} bytes buffered", buffered);
+        }
+    }
+
+    // `max_size` only counts the records' values, not their keys nor
+    // leveldb's own framing, so neither does the estimate. This checks it
+    // against the values actually stored in the data directory.
+    #[tokio::test]
+    async fn estimate_size_matches_stored_size() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("buffer");
+        let events = (1..=10)
+            .map(|i| Event::from("event ".repeat(i)))
+            .collect::<Vec<_>>();
+        let estimate = estimate_size(&events);
+
+        {
+            let (mut writer, _reader, _acker) =
+                Buffer::build_with_memory_budget(path.clone(), 1_000_000, None).unwrap();
+            for event in events {
+                writer.send(event).await.unwrap();
+            }
+        }
+
+        let stored = records(&path)
+            .into_iter()
+            .map(|(_key, value)| value.len())
+            .sum::<usize>();
+        assert_eq!(stored, estimate);
+    }
+
+    fn records(path: &Path) -> Vec<(usize, Vec<u8>)> {
+        let db: Database<Key> = Database::open(path, Options::new()).unwrap();
+        db.iter(ReadOptions::new())
+            .map(|(key, value)| (key.0, value))
+            .collect()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn stream_out_and_import_round_trip() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("buffer");
+        let imported = dir.path().join("imported");
+
+        {
+            let (mut writer, mut reader, acker) =
+                Buffer::build_with_memory_budget(path.clone(), 1_000_000, None).unwrap();
+            for i in 0..250 {
+                writer
+                    .send(Event::from(format!("event {}", i)))
+                    .await
+                    .unwrap();
+            }
+            // Leave a gap at the start of the key range.
+            reader.next().await.unwrap();
+            acker.ack(1);
+            reader.next().await.unwrap();
+        }
+
+        let (tx, rx) = tokio::io::duplex(64);
+        let (sent, received) = tokio::join!(stream_out(&path, tx), import_stream(rx, &imported));
+        assert_eq!(sent.unwrap(), 249);
+        assert_eq!(received.unwrap(), 249);
+
+        let original = records(&path);
+        assert_eq!(original.first().unwrap().0, 1);
+        assert_eq!(records(&imported), original);
+    }
+
+    #[tokio::test]
+    async fn import_rejects_truncated_record() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("buffer");
+
+        // A record claiming to be far larger than the stream.
+        let mut stream = STREAM_MAGIC.to_vec();
+        stream.push(STREAM_VERSION);
+        stream.push(STREAM_RECORD);
+        stream.extend_from_slice(&0_u64.to_be_bytes());
+        stream.extend_from_slice(&(1_u64 << 40).to_be_bytes());
+        stream.extend_from_slice(b"not nearly enough");
+
+        let error = import_stream(&stream[..], &path).await.unwrap_err();
+        assert!(
+            matches!(error, super::Error::InvalidStream { .. }),
+            "{}",
+            error
+        );
+        // Nothing is left behind to get in the way of importing again.
+        assert!(!path.exists());
+    }
+}
@@ -1,16 +1,23 @@
 use crate::event::Event;
-use futures::{Sink, Stream};
+use futures::{Sink, Stream, StreamExt};
 use pin_project::pin_project;
 use snafu::Snafu;
 use std::{
     io,
     path::{Path, PathBuf},
     pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize},
+        Arc,
+    },
     task::{Context, Poll},
 };
+use tokio::io::{AsyncRead, AsyncWrite};
 
 pub mod leveldb_buffer;
 
+pub use leveldb_buffer::LowSpace;
+
 #[derive(Debug, Snafu)]
 #[allow(clippy::pub_enum_variant_names)]
 pub enum Error {
@@ -28,6 +35,27 @@ pub enum Error {
         data_dir: PathBuf,
         source: leveldb::database::error::Error,
     },
+    #[snafu(display("Unable to stream disk buffer records"))]
+    StreamError { source: io::Error },
+    #[snafu(display("Invalid disk buffer stream: {}", reason))]
+    InvalidStream { reason: &'static str },
+    #[snafu(display("Unable to write imported records"))]
+    ImportWriteError {
+        source: leveldb::database::error::Error,
+    },
+}
+
+impl Error {
+    /// Whether opening the buffer again later may succeed, for example once
+    /// the filesystem holding the data dir has been mounted.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Error::DataDirNotFound { .. }
+                | Error::DataDirMetadataError { .. }
+                | Error::DataDirOpenError { .. }
+        )
+    }
 }
 
 pub trait DiskBuffer {
@@ -53,6 +81,32 @@ pub struct Writer {
     inner: leveldb_buffer::Writer,
 }
 
+impl Writer {
+    /// Drop events which do not fit into the buffer rather than holding on
+    /// to them until enough space is freed.
+    pub fn set_drop_when_full(&mut self, drop_when_full: bool) {
+        self.inner.set_drop_when_full(drop_when_full);
+    }
+
+    /// Increment `dropped_events` for every event dropped because it did not
+    /// fit into the buffer.
+    pub fn set_dropped_events(&mut self, dropped_events: Arc<AtomicU64>) {
+        self.inner.set_dropped_events(dropped_events);
+    }
+
+    /// A handle to refuse events, as if the buffer were full, while the
+    /// disk is low on space.
+    pub fn low_space(&self) -> LowSpace {
+        self.inner.low_space()
+    }
+
+    /// The size of the events stored in the buffer, shared with the other
+    /// writers and the reader.
+    pub fn current_size(&self) -> Arc<AtomicUsize> {
+        self.inner.current_size()
+    }
+}
+
 impl Sink<Event> for Writer {
     type Error = ();
     fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -74,6 +128,10 @@ impl Sink<Event> for Writer {
 
 /// Open a [`leveldb_buffer::Buffer`]
 ///
+/// When `reader_memory_budget` is set, the reader holds at most that many
+/// bytes of records in memory at once, beyond the single record it is
+/// yielding.
+///
 /// # Errors
 ///
 /// This function will fail with [`Error`] if the directory does not exist at
@@ -82,6 +140,7 @@ pub fn open(
     data_dir: &Path,
     name: &str,
     max_size: usize,
+    reader_memory_budget: Option<usize>,
 ) -> Result<(Writer, Box<dyn Stream<Item = Event> + Send>, super::Acker), Error> {
     let path = data_dir.join(name);
 
@@ -109,6 +168,66 @@ pub fn open(
             }
         })?;
 
-    let (writer, reader, acker) = leveldb_buffer::Buffer::build(path, max_size)?;
+    let (writer, reader, acker) =
+        leveldb_buffer::Buffer::build_with_memory_budget(path, max_size, reader_memory_budget)?;
     Ok((Writer { inner: writer }, Box::new(reader), acker))
 }
+
+/// The smallest `max_size` a disk buffer can hold any event at all with.
+/// Writers to a smaller buffer block forever.
+pub fn min_max_size() -> usize {
+    leveldb_buffer::min_max_size()
+}
+
+/// Ack every event as soon as `reader` yields it, for `AckPolicy::OnRead`.
+///
+/// The buffer deletes acked events the next time it is read from or when it
+/// is dropped, so after a crash at most the last event read is read again.
+pub fn ack_on_read(
+    reader: Box<dyn Stream<Item = Event> + Send>,
+    acker: super::Acker,
+) -> Box<dyn Stream<Item = Event> + Send> {
+    // Forward rather than ack, the sink still counts the events it sends.
+    Box::new(Pin::from(reader).inspect(move |_| acker.forward(1)))
+}
+
+/// Estimate how many bytes `events` would take up against a disk buffer's
+/// `max_size`, without writing them.
+///
+/// Like `max_size`, this counts the encoded events only. The keys and
+/// framing leveldb stores alongside them are not included, so the buffer's
+/// data directory grows somewhat larger.
+///
+/// The buffer keeps a tenth of `max_size` for deleted records awaiting
+/// compaction, so a buffer needs a `max_size` of about `10 / 9` times this
+/// estimate to hold all of `events` at once.
+pub fn estimate_size(events: &[Event]) -> usize {
+    events.iter().map(leveldb_buffer::encoded_size).sum()
+}
+
+/// Stream every record of the disk buffer at `path` to `writer` in a
+/// self-describing framed format, returning the number of records written.
+/// [`import_stream`] reconstructs the buffer on the other end.
+///
+/// The buffer must not be open while it is streamed.
+///
+/// # Errors
+///
+/// This function will fail with [`Error`] if the buffer cannot be opened or
+/// writing to `writer` fails.
+pub async fn stream_out<W: AsyncWrite + Unpin>(path: &Path, writer: W) -> Result<usize, Error> {
+    leveldb_buffer::stream_out(path, writer).await
+}
+
+/// Reconstruct a disk buffer at `path` from records streamed by
+/// [`stream_out`], returning the number of records read. Records keep their
+/// positions, so the new buffer reads back exactly like the original.
+///
+/// # Errors
+///
+/// This function will fail with [`Error`] if a buffer already exists at
+/// `path`, or `reader` does not yield a complete stream. A buffer it fails to
+/// finish importing is removed again.
+pub async fn import_stream<R: AsyncRead + Unpin>(reader: R, path: &Path) -> Result<usize, Error> {
+    leveldb_buffer::import_stream(reader, path).await
+}
@@ -0,0 +1,201 @@
+use futures::{Sink, Stream};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+    task::{Context, Poll, Waker},
+};
+
+/// Create a channel holding up to `capacity` items which, once full, drops
+/// the oldest queued item to make room for each new one.
+///
+/// Sends never apply backpressure. The receiver yields the remaining items
+/// after every sender is closed or dropped.
+pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+    let shared = Arc::new(Mutex::new(Shared {
+        queue: VecDeque::with_capacity(capacity),
+        senders: 1,
+        receiver: None,
+    }));
+    let dropped = Arc::new(AtomicU64::new(0));
+
+    let tx = Sender {
+        shared: Arc::clone(&shared),
+        capacity,
+        dropped: Arc::clone(&dropped),
+        closed: false,
+    };
+    let rx = Receiver { shared };
+    (tx, rx)
+}
+
+struct Shared<T> {
+    queue: VecDeque<T>,
+    senders: usize,
+    receiver: Option<Waker>,
+}
+
+impl<T> Shared<T> {
+    fn wake_receiver(&mut self) {
+        if let Some(waker) = self.receiver.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Sender<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+    capacity: usize,
+    dropped: Arc<AtomicU64>,
+    closed: bool,
+}
+
+impl<T> Sender<T> {
+    fn close(&mut self) {
+        if !self.closed {
+            self.closed = true;
+            let mut shared = self.shared.lock().unwrap();
+            shared.senders -= 1;
+            if shared.senders == 0 {
+                // Let the receiver see that no more items are coming.
+                shared.wake_receiver();
+            }
+        }
+    }
+
+    /// The number of items dropped to make room for newer ones, shared
+    /// between all senders of the channel.
+    pub fn dropped_events(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.dropped)
+    }
+}
+
+// Deriving `Clone` would needlessly require `T: Clone`.
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().unwrap().senders += 1;
+        Self {
+            shared: Arc::clone(&self.shared),
+            capacity: self.capacity,
+            dropped: Arc::clone(&self.dropped),
+            closed: false,
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+impl<T> Sink<T> for Sender<T> {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        let mut shared = self.shared.lock().unwrap();
+        if shared.queue.len() >= self.capacity {
+            debug!(
+                message = "Shedding load; dropping oldest event.",
+                internal_log_rate_secs = 10
+            );
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            if shared.queue.pop_front().is_none() {
+                // There is no room for anything at all.
+                return Ok(());
+            }
+        }
+        shared.queue.push_back(item);
+        shared.wake_receiver();
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(
+        mut self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<(), Self::Error>> {
+        // Whatever is queued stays queued for the receiver.
+        self.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+pub struct Receiver<T> {
+    shared: Arc<Mutex<Shared<T>>>,
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut shared = self.shared.lock().unwrap();
+        match shared.queue.pop_front() {
+            Some(item) => Poll::Ready(Some(item)),
+            None if shared.senders == 0 => Poll::Ready(None),
+            None => {
+                shared.receiver = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::channel;
+    use futures::{future, Sink, Stream};
+    use std::task::Poll;
+
+    #[tokio::test]
+    async fn capacity_one_keeps_most_recent() {
+        future::lazy(|cx| {
+            let (tx, rx) = channel(1);
+
+            let mut tx = Box::pin(tx);
+
+            for item in 1..=3 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(tx.as_mut().start_send(item), Ok(()));
+            }
+
+            let mut rx = Box::pin(rx);
+
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(3)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn close_keeps_queued_items() {
+        future::lazy(|cx| {
+            let (tx, rx) = channel(2);
+
+            let mut tx = Box::pin(tx);
+
+            for item in 1..=2 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(tx.as_mut().start_send(item), Ok(()));
+            }
+            assert_eq!(tx.as_mut().poll_close(cx), Poll::Ready(Ok(())));
+
+            let mut rx = Box::pin(rx);
+
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(1)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(2)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(None));
+        })
+        .await;
+    }
+}
@@ -0,0 +1,243 @@
+use super::Acker;
+use crate::event::{Event, Value};
+use chrono::{DateTime, Duration, Utc};
+use futures::{task::AtomicWaker, Stream};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// What to do with events whose timestamp field is missing, or holds
+/// neither a timestamp nor an RFC 3339 string.
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidTimestamp {
+    Keep,
+    Drop,
+}
+
+impl Default for InvalidTimestamp {
+    fn default() -> Self {
+        InvalidTimestamp::Keep
+    }
+}
+
+/// Wraps a buffer reader, dropping the events whose timestamp `field` is
+/// more than `ttl` in the past as they are read.
+///
+/// Acks given to [`Reader::acker`] are passed on to the buffer together with
+/// those for the events dropped before them, so the buffer keeps acking in
+/// order.
+pub struct Reader {
+    inner: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    inner_acker: Acker,
+    field: String,
+    ttl: Duration,
+    on_invalid: InvalidTimestamp,
+    /// Whether each read, but not yet acked, event was dropped. Only kept
+    /// when the buffer has acks to pass on.
+    unacked: Option<VecDeque<bool>>,
+    ack_counter: Arc<AtomicUsize>,
+    ack_notifier: Arc<AtomicWaker>,
+}
+
+impl Reader {
+    pub fn new(
+        inner: Box<dyn Stream<Item = Event> + Send>,
+        inner_acker: Acker,
+        field: String,
+        ttl: std::time::Duration,
+        on_invalid: InvalidTimestamp,
+    ) -> Self {
+        let unacked = match inner_acker {
+            Acker::Disk(..) => Some(VecDeque::new()),
+            Acker::Null => None,
+        };
+        Self {
+            inner: inner.into(),
+            inner_acker,
+            field,
+            ttl: Duration::from_std(ttl).unwrap_or_else(|_| Duration::max_value()),
+            on_invalid,
+            unacked,
+            ack_counter: Arc::new(AtomicUsize::new(0)),
+            ack_notifier: Arc::new(AtomicWaker::new()),
+        }
+    }
+
+    /// The acker for events yielded by this reader.
+    pub fn acker(&self) -> Acker {
+        match self.unacked {
+            Some(_) => Acker::Disk(
+                Arc::clone(&self.ack_counter),
+                Arc::clone(&self.ack_notifier),
+            ),
+            None => Acker::Null,
+        }
+    }
+
+    fn is_expired(&self, event: &Event, now: DateTime<Utc>) -> bool {
+        let timestamp = match event {
+            Event::Log(log) => match log.get(&self.field) {
+                Some(Value::Timestamp(timestamp)) => Some(*timestamp),
+                Some(Value::Bytes(bytes)) => std::str::from_utf8(bytes)
+                    .ok()
+                    .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+                    .map(|timestamp| timestamp.with_timezone(&Utc)),
+                _ => None,
+            },
+            Event::Metric(_) => None,
+        };
+        match timestamp {
+            Some(timestamp) => timestamp
+                .checked_add_signed(self.ttl)
+                .map_or(false, |expiry| expiry < now),
+            None => self.on_invalid == InvalidTimestamp::Drop,
+        }
+    }
+
+    fn forward_acks(&mut self) {
+        let unacked = match &mut self.unacked {
+            Some(unacked) => unacked,
+            None => return,
+        };
+        let mut acked = self.ack_counter.swap(0, Ordering::Relaxed);
+
+        // Pass on the acked events and the dropped events read before them,
+        // and any dropped events that are next in line after that.
+        let mut num = 0;
+        while let Some(&dropped) = unacked.front() {
+            if !dropped {
+                if acked == 0 {
+                    break;
+                }
+                acked -= 1;
+            }
+            unacked.pop_front();
+            num += 1;
+        }
+        self.inner_acker.forward(num);
+    }
+}
+
+impl Stream for Reader {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.ack_notifier.register(cx.waker());
+        self.forward_acks();
+
+        let now = Utc::now();
+        loop {
+            let event = match futures::ready!(self.inner.as_mut().poll_next(cx)) {
+                Some(event) => event,
+                None => return Poll::Ready(None),
+            };
+            let expired = self.is_expired(&event, now);
+            if let Some(unacked) = &mut self.unacked {
+                unacked.push_back(expired);
+            }
+            if !expired {
+                return Poll::Ready(Some(event));
+            }
+            debug!(
+                message = "Dropping expired event.",
+                field = %self.field,
+                internal_log_rate_secs = 10
+            );
+            self.forward_acks();
+        }
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        // Let the buffer delete whatever was acked before it shuts down.
+        self.forward_acks();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{InvalidTimestamp, Reader};
+    use crate::buffers::Acker;
+    use crate::event::Event;
+    use chrono::{Duration, Utc};
+    use futures::{stream, StreamExt};
+    use std::sync::atomic::Ordering;
+
+    fn event(message: &str, age: Option<Duration>) -> Event {
+        let mut event = Event::from(message);
+        if let Some(age) = age {
+            event.as_mut_log().insert("ts", Utc::now() - age);
+        }
+        event
+    }
+
+    #[tokio::test]
+    async fn drops_expired_events() {
+        let events = vec![
+            event("old", Some(Duration::hours(2))),
+            event("fresh", Some(Duration::minutes(1))),
+            event("missing", None),
+            event("older", Some(Duration::days(1))),
+            event("fresher", Some(Duration::zero())),
+        ];
+        let reader = Reader::new(
+            Box::new(stream::iter(events)),
+            Acker::Null,
+            "ts".into(),
+            std::time::Duration::from_secs(3600),
+            InvalidTimestamp::Drop,
+        );
+
+        let messages = reader
+            .map(|event| event.as_log()["message"].to_string_lossy())
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(messages, vec!["fresh", "fresher"]);
+    }
+
+    #[tokio::test]
+    async fn acks_dropped_events_in_order() {
+        let events = vec![
+            event("old", Some(Duration::hours(2))),
+            event("fresh", None),
+            event("old", Some(Duration::hours(2))),
+            event("fresh", None),
+            event("old", Some(Duration::hours(2))),
+        ];
+        let (inner_acker, acks) = Acker::new_for_testing();
+        let mut reader = Reader::new(
+            Box::new(stream::iter(events)),
+            inner_acker,
+            "ts".into(),
+            std::time::Duration::from_secs(3600),
+            InvalidTimestamp::Keep,
+        );
+        let acker = reader.acker();
+
+        // The leading dropped event needs no ack from the sink.
+        assert!(reader.next().await.is_some());
+        assert_eq!(acks.load(Ordering::Relaxed), 1);
+
+        // Nor does the one after the first yielded event, but it waits for
+        // that event to be acked.
+        assert!(reader.next().await.is_some());
+        assert_eq!(acks.load(Ordering::Relaxed), 1);
+
+        acker.ack(1);
+        assert!(reader.next().await.is_none());
+        assert_eq!(acks.load(Ordering::Relaxed), 3);
+
+        acker.ack(1);
+        drop(reader);
+        assert_eq!(acks.load(Ordering::Relaxed), 5);
+    }
+}
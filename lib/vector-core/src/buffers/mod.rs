@@ -1,22 +1,43 @@
 mod acker;
+pub mod byte_capped;
+pub mod compressed;
 #[cfg(feature = "disk-buffer")]
 pub mod disk;
+pub mod drop_oldest;
+pub mod expiring;
+#[cfg(feature = "disk-buffer")]
+pub mod overflow;
+mod rate_budget;
+mod recent;
+pub mod soft_cap;
+mod usage;
 
 use crate::event::Event;
 pub use acker::Acker;
-use futures::{channel::mpsc, Sink, SinkExt};
+use futures::{channel::mpsc, future, Future, Sink, SinkExt, Stream, StreamExt};
 use pin_project::pin_project;
+pub use rate_budget::{RateBudget, RateLimited};
+pub use recent::Recent;
 use serde::{Deserialize, Serialize};
 use std::{
     pin::Pin,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
     task::{Context, Poll},
+    time::Duration,
 };
+use tokio::time::{sleep, Sleep};
+pub use usage::BufferUsage;
 
 #[derive(Deserialize, Serialize, Debug, PartialEq, Copy, Clone)]
 #[serde(rename_all = "snake_case")]
 pub enum WhenFull {
     Block,
     DropNewest,
+    DropOldest,
+    Overflow,
 }
 
 impl Default for WhenFull {
@@ -25,40 +46,162 @@ impl Default for WhenFull {
     }
 }
 
-// Clippy warns that the `Disk` variant below is much larger than the
-// `Memory` variant (currently 233 vs 25 bytes) and recommends boxing
-// the large fields to reduce the total size.
-#[allow(clippy::large_enum_variant)]
+/// When a disk buffer may delete the events it has yielded.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum AckPolicy {
+    /// Once the sink acks them, after it has delivered them.
+    OnConfirm,
+    /// As soon as the reader yields them, so events the sink has not
+    /// delivered yet are lost if Vector stops.
+    OnRead,
+}
+
+impl Default for AckPolicy {
+    fn default() -> Self {
+        AckPolicy::OnConfirm
+    }
+}
+
+/// The channel backing a memory buffer.
+#[derive(Deserialize, Serialize, Debug, PartialEq, Copy, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ChannelKind {
+    /// Holds up to `max_events` events.
+    Bounded,
+    /// Holds no shared capacity, every event is handed off directly to the
+    /// reader. Each input may still have a single event waiting for handoff.
+    Rendezvous,
+    /// Never blocks, but sheds new events once `max_events` events are
+    /// queued, whatever `when_full` is.
+    UnboundedWithSoftCap,
+}
+
+impl Default for ChannelKind {
+    fn default() -> Self {
+        ChannelKind::Bounded
+    }
+}
+
+/// The last field of the `Memory`, `ByteCapped`, `Compressed` and `Disk`
+/// variants counts the events dropped by their `WhenFull::DropNewest` policy,
+/// shared between all inputs. `SoftCapped` and `DropOldest` channels count
+/// the events they shed themselves.
+///
+/// `BlockTimeout` wraps the inputs of another buffer in [`BlockTimeout`],
+/// sharing its last field as their dropped event count, which is the wrapped
+/// buffer's own count where it has one. `Counted` counts the events sent to
+/// the inputs of another buffer for [`BufferUsage`].
+///
+/// The disk writers are boxed because they are much larger than the memory
+/// senders, and would otherwise make every `BufferInputCloner` as large.
 #[derive(Clone)]
 pub enum BufferInputCloner {
-    Memory(mpsc::Sender<Event>, WhenFull),
+    Memory(mpsc::Sender<Event>, WhenFull, Arc<AtomicU64>),
+    SoftCapped(soft_cap::Sender<Event>),
+    ByteCapped(byte_capped::Sender, WhenFull, Arc<AtomicU64>),
+    Compressed(compressed::Sender, WhenFull, Arc<AtomicU64>),
+    DropOldest(drop_oldest::Sender<Event>),
+    #[cfg(feature = "disk-buffer")]
+    Disk(Box<disk::Writer>, WhenFull, Arc<AtomicU64>),
     #[cfg(feature = "disk-buffer")]
-    Disk(disk::Writer, WhenFull),
+    Overflow(mpsc::Sender<Event>, Box<disk::Writer>),
+    BlockTimeout(Box<BufferInputCloner>, Duration, Arc<AtomicU64>),
+    Counted(Box<BufferInputCloner>, Arc<AtomicU64>),
 }
 
 impl BufferInputCloner {
     pub fn get(&self) -> Box<dyn Sink<Event, Error = ()> + Send> {
         match self {
-            BufferInputCloner::Memory(tx, when_full) => {
+            BufferInputCloner::Memory(tx, when_full, dropped_events) => {
                 let inner = tx
                     .clone()
                     .sink_map_err(|error| error!(message = "Sender error.", %error));
                 if when_full == &WhenFull::DropNewest {
-                    Box::new(DropWhenFull::new(inner))
+                    Box::new(DropWhenFull::with_counter(
+                        inner,
+                        Arc::clone(dropped_events),
+                    ))
                 } else {
                     Box::new(inner)
                 }
             }
 
-            #[cfg(feature = "disk-buffer")]
-            BufferInputCloner::Disk(writer, when_full) => {
-                let inner = writer.clone();
+            BufferInputCloner::SoftCapped(tx) => Box::new(tx.clone()),
+
+            BufferInputCloner::DropOldest(tx) => Box::new(tx.clone()),
+
+            BufferInputCloner::ByteCapped(tx, when_full, dropped_events) => {
+                let inner = tx.clone();
+                if when_full == &WhenFull::DropNewest {
+                    Box::new(DropWhenFull::with_counter(
+                        inner,
+                        Arc::clone(dropped_events),
+                    ))
+                } else {
+                    Box::new(inner)
+                }
+            }
+
+            BufferInputCloner::Compressed(tx, when_full, dropped_events) => {
+                let inner = tx.clone();
                 if when_full == &WhenFull::DropNewest {
-                    Box::new(DropWhenFull::new(inner))
+                    Box::new(DropWhenFull::with_counter(
+                        inner,
+                        Arc::clone(dropped_events),
+                    ))
                 } else {
                     Box::new(inner)
                 }
             }
+
+            #[cfg(feature = "disk-buffer")]
+            BufferInputCloner::Disk(writer, when_full, dropped_events) => {
+                // The writer checks whether each event fits before writing
+                // it, so it applies the drop policy itself.
+                let mut inner = disk::Writer::clone(writer);
+                inner.set_drop_when_full(when_full == &WhenFull::DropNewest);
+                inner.set_dropped_events(Arc::clone(dropped_events));
+                Box::new(inner)
+            }
+
+            #[cfg(feature = "disk-buffer")]
+            BufferInputCloner::Overflow(tx, writer) => Box::new(overflow::Sender::new(
+                tx.clone(),
+                disk::Writer::clone(writer),
+            )),
+
+            BufferInputCloner::BlockTimeout(inner, timeout, dropped_events) => {
+                Box::new(BlockTimeout::with_counter(
+                    Pin::from(inner.get()),
+                    *timeout,
+                    Arc::clone(dropped_events),
+                ))
+            }
+
+            BufferInputCloner::Counted(inner, sent) => {
+                Box::new(usage::CountSent::new(inner.get(), Arc::clone(sent)))
+            }
+        }
+    }
+
+    /// The number of events this buffer's inputs have dropped because the
+    /// buffer was full, or `None` for buffers that do not drop events that
+    /// way.
+    pub fn dropped_events(&self) -> Option<Arc<AtomicU64>> {
+        match self {
+            BufferInputCloner::Memory(_, _, dropped_events)
+            | BufferInputCloner::ByteCapped(_, _, dropped_events)
+            | BufferInputCloner::Compressed(_, _, dropped_events)
+            | BufferInputCloner::BlockTimeout(_, _, dropped_events) => {
+                Some(Arc::clone(dropped_events))
+            }
+            BufferInputCloner::SoftCapped(tx) => Some(tx.dropped_events()),
+            BufferInputCloner::DropOldest(tx) => Some(tx.dropped_events()),
+            #[cfg(feature = "disk-buffer")]
+            BufferInputCloner::Disk(_, _, dropped_events) => Some(Arc::clone(dropped_events)),
+            BufferInputCloner::Counted(inner, _) => inner.dropped_events(),
+            _ => None,
         }
     }
 }
@@ -68,11 +211,27 @@ pub struct DropWhenFull<S> {
     #[pin]
     inner: S,
     drop: bool,
+    dropped_events: Arc<AtomicU64>,
 }
 
 impl<S> DropWhenFull<S> {
     pub fn new(inner: S) -> Self {
-        Self { inner, drop: false }
+        Self::with_counter(inner, Default::default())
+    }
+
+    /// Like [`DropWhenFull::new`], counting dropped events in
+    /// `dropped_events` so the count can be shared with other inputs.
+    pub fn with_counter(inner: S, dropped_events: Arc<AtomicU64>) -> Self {
+        Self {
+            inner,
+            drop: false,
+            dropped_events,
+        }
+    }
+
+    /// The number of events dropped because `inner` was full.
+    pub fn dropped_events(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.dropped_events)
     }
 }
 
@@ -100,6 +259,125 @@ impl<T, S: Sink<T> + Unpin> Sink<T> for DropWhenFull<S> {
                 message = "Shedding load; dropping event.",
                 internal_log_rate_secs = 10
             );
+            self.dropped_events.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        } else {
+            self.project().inner.start_send(item)
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+/// Take the events already queued in a buffer's reader, for example to
+/// flush them on shutdown.
+///
+/// Stops once the queue is empty, rather than wait for more events, or after
+/// `max_events`, the most the buffer could hold when the drain started, so a
+/// source still sending to the buffer can't keep it going. Gives up on the
+/// rest once `timeout` has elapsed. Events drained from a disk buffer are not
+/// acked, so unless they are acked through its `Acker` they are read again
+/// after a restart.
+pub async fn drain<S: Stream<Item = Event> + Unpin>(
+    mut rx: S,
+    max_events: usize,
+    timeout: Duration,
+) -> Vec<Event> {
+    let mut events = Vec::new();
+    let take = async {
+        while events.len() < max_events {
+            let next = future::poll_fn(|cx| match rx.poll_next_unpin(cx) {
+                Poll::Pending => Poll::Ready(None),
+                ready => ready,
+            })
+            .await;
+            match next {
+                Some(event) => events.push(event),
+                None => break,
+            }
+            // Give the timeout a chance to fire between events.
+            tokio::task::yield_now().await;
+        }
+    };
+    // The events taken before the timeout are returned all the same.
+    let _ = tokio::time::timeout(timeout, take).await;
+    events
+}
+
+/// Applies the back pressure of `inner` for up to `timeout` per stall, then
+/// drops events until `inner` is ready again.
+///
+/// The timer starts the first time `inner` is found not ready, and is reset
+/// once it is ready again.
+#[pin_project]
+pub struct BlockTimeout<S> {
+    #[pin]
+    inner: S,
+    timeout: Duration,
+    stall: Option<Pin<Box<Sleep>>>,
+    drop: bool,
+    dropped_events: Arc<AtomicU64>,
+}
+
+impl<S> BlockTimeout<S> {
+    pub fn new(inner: S, timeout: Duration) -> Self {
+        Self::with_counter(inner, timeout, Default::default())
+    }
+
+    /// Like [`BlockTimeout::new`], counting dropped events in
+    /// `dropped_events` so the count can be shared with other inputs.
+    pub fn with_counter(inner: S, timeout: Duration, dropped_events: Arc<AtomicU64>) -> Self {
+        Self {
+            inner,
+            timeout,
+            stall: None,
+            drop: false,
+            dropped_events,
+        }
+    }
+
+    /// The number of events dropped because `inner` stayed full for longer
+    /// than the timeout.
+    pub fn dropped_events(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.dropped_events)
+    }
+}
+
+impl<T, S: Sink<T> + Unpin> Sink<T> for BlockTimeout<S> {
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        match this.inner.poll_ready(cx) {
+            Poll::Ready(Ok(())) => {
+                *this.stall = None;
+                *this.drop = false;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Pending => {
+                let timeout = *this.timeout;
+                let stall = this.stall.get_or_insert_with(|| Box::pin(sleep(timeout)));
+                futures::ready!(stall.as_mut().poll(cx));
+                *this.drop = true;
+                Poll::Ready(Ok(()))
+            }
+            error => error,
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        if self.drop {
+            debug!(
+                message = "Shedding load; dropping event after blocking timed out.",
+                internal_log_rate_secs = 10
+            );
+            self.dropped_events.fetch_add(1, Ordering::Relaxed);
             Ok(())
         } else {
             self.project().inner.start_send(item)
@@ -117,12 +395,18 @@ impl<T, S: Sink<T> + Unpin> Sink<T> for DropWhenFull<S> {
 
 #[cfg(test)]
 mod test {
-    use super::{Acker, DropWhenFull};
-    use futures::{channel::mpsc, future, task::AtomicWaker, Sink, Stream};
+    use super::{drain, drop_oldest, Acker, BlockTimeout, DropWhenFull};
+    use crate::event::Event;
+    use futures::{channel::mpsc, future, task::AtomicWaker, Sink, SinkExt, Stream};
     use std::{
-        sync::{atomic::AtomicUsize, Arc},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
         task::Poll,
+        time::Duration,
     };
+    use tokio::time;
     use tokio_test::task::spawn;
 
     #[tokio::test]
@@ -131,6 +415,7 @@ mod test {
             let (tx, rx) = mpsc::channel(2);
 
             let mut tx = Box::pin(DropWhenFull::new(tx));
+            let dropped_events = tx.dropped_events();
 
             assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
             assert_eq!(tx.as_mut().start_send(1), Ok(()));
@@ -147,8 +432,135 @@ mod test {
             assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(2)));
             assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(3)));
             assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
+
+            assert_eq!(dropped_events.load(Ordering::Relaxed), 1);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn drop_oldest() {
+        future::lazy(|cx| {
+            let (tx, rx) = drop_oldest::channel(2);
+            let dropped_events = tx.dropped_events();
+
+            let mut tx = Box::pin(tx);
+
+            for item in 1..=4 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(tx.as_mut().start_send(item), Ok(()));
+            }
+
+            let mut rx = Box::pin(rx);
+
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(3)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(4)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
+
+            assert_eq!(dropped_events.load(Ordering::Relaxed), 2);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn block_timeout_drops_once_timed_out() {
+        time::pause();
+
+        let (tx, rx) = mpsc::channel(0);
+        let mut tx = Box::pin(BlockTimeout::new(tx, Duration::from_secs(5)));
+        let dropped_events = tx.dropped_events();
+
+        future::lazy(|cx| {
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(1), Ok(()));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+        })
+        .await;
+
+        time::advance(Duration::from_secs(6)).await;
+
+        future::lazy(|cx| {
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(2), Ok(()));
+
+            let mut rx = Box::pin(rx);
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(1)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
         })
         .await;
+
+        assert_eq!(dropped_events.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn block_timeout_blocks_until_timed_out() {
+        time::pause();
+
+        let (tx, rx) = mpsc::channel(0);
+        let mut tx = Box::pin(BlockTimeout::new(tx, Duration::from_secs(5)));
+        let mut rx = Box::pin(rx);
+        let dropped_events = tx.dropped_events();
+
+        future::lazy(|cx| {
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(1), Ok(()));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+        })
+        .await;
+
+        time::advance(Duration::from_secs(4)).await;
+
+        future::lazy(|cx| {
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+
+            // Making room ends the stall, so the next one gets a full timeout.
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(1)));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(2), Ok(()));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+        })
+        .await;
+
+        time::advance(Duration::from_secs(4)).await;
+
+        future::lazy(|cx| {
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Pending);
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(2)));
+        })
+        .await;
+
+        assert_eq!(dropped_events.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn drain_takes_queued_events() {
+        let (mut tx, rx) = mpsc::channel(10);
+        let events = vec![Event::from("a"), Event::from("b"), Event::from("c")];
+        for event in events.clone() {
+            tx.send(event).await.unwrap();
+        }
+
+        // The sender is still open, draining must not wait for it.
+        assert_eq!(drain(rx, 10, Duration::from_secs(60)).await, events);
+    }
+
+    #[tokio::test]
+    async fn drain_empty_returns_immediately() {
+        let (_tx, rx) = mpsc::channel::<Event>(10);
+        let start = std::time::Instant::now();
+
+        assert!(drain(rx, 10, Duration::from_secs(60)).await.is_empty());
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn drain_stops_at_max_events() {
+        let (mut tx, rx) = mpsc::channel(10);
+        for _ in 0..5 {
+            tx.send(Event::from("a")).await.unwrap();
+        }
+
+        assert_eq!(drain(rx, 3, Duration::from_secs(60)).await.len(), 3);
     }
 
     #[test]
@@ -0,0 +1,177 @@
+use super::{disk, Acker};
+use crate::event::Event;
+use futures::{
+    channel::mpsc,
+    stream::{Fuse, FusedStream},
+    task::AtomicWaker,
+    Sink, Stream, StreamExt,
+};
+use pin_project::pin_project;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Target {
+    Memory,
+    Disk,
+}
+
+/// Sends events to a memory channel, spilling them to a disk buffer while
+/// the channel is full.
+#[pin_project]
+pub struct Sender {
+    #[pin]
+    memory: mpsc::Sender<Event>,
+    #[pin]
+    disk: disk::Writer,
+    target: Target,
+}
+
+impl Sender {
+    pub fn new(memory: mpsc::Sender<Event>, disk: disk::Writer) -> Self {
+        Self {
+            memory,
+            disk,
+            target: Target::Memory,
+        }
+    }
+}
+
+impl Sink<Event> for Sender {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        match this.memory.poll_ready(cx) {
+            Poll::Ready(Ok(())) => {
+                *this.target = Target::Memory;
+                Poll::Ready(Ok(()))
+            }
+            Poll::Ready(Err(error)) => {
+                error!(message = "Sender error.", %error);
+                Poll::Ready(Err(()))
+            }
+            Poll::Pending => {
+                *this.target = Target::Disk;
+                this.disk.poll_ready(cx)
+            }
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Event) -> Result<(), Self::Error> {
+        let this = self.project();
+        match this.target {
+            Target::Memory => this
+                .memory
+                .start_send(item)
+                .map_err(|error| error!(message = "Sender error.", %error)),
+            Target::Disk => this.disk.start_send(item),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        // Events sent to the channel are already queued, flushing it would
+        // only wait for room in it, which is what spilling avoids.
+        self.project().disk.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.project();
+        futures::ready!(this.disk.poll_close(cx))?;
+        this.memory
+            .poll_close(cx)
+            .map_err(|error| error!(message = "Sender error.", %error))
+    }
+}
+
+/// Reads the events of a memory channel first, and those spilled to its
+/// disk buffer once the channel is empty.
+///
+/// Events spilled to disk are read after any that were sent to the channel
+/// later, while it had room again. Acks given to [`Reader::acker`] are
+/// passed on to the disk buffer for the spilled events only.
+pub struct Reader {
+    memory: mpsc::Receiver<Event>,
+    disk: Fuse<Box<dyn Stream<Item = Event> + Send>>,
+    disk_acker: Acker,
+    /// Whether each yielded, but not yet acked, event was read from disk.
+    unacked: VecDeque<Target>,
+    ack_counter: Arc<AtomicUsize>,
+    ack_notifier: Arc<AtomicWaker>,
+}
+
+impl Reader {
+    pub fn new(
+        memory: mpsc::Receiver<Event>,
+        disk: Box<dyn Stream<Item = Event> + Send>,
+        disk_acker: Acker,
+    ) -> Self {
+        Self {
+            memory,
+            disk: disk.fuse(),
+            disk_acker,
+            unacked: VecDeque::new(),
+            ack_counter: Arc::new(AtomicUsize::new(0)),
+            ack_notifier: Arc::new(AtomicWaker::new()),
+        }
+    }
+
+    /// The acker for events yielded by this reader.
+    pub fn acker(&self) -> Acker {
+        Acker::Disk(
+            Arc::clone(&self.ack_counter),
+            Arc::clone(&self.ack_notifier),
+        )
+    }
+
+    fn forward_acks(&mut self) {
+        let num = self.ack_counter.swap(0, Ordering::Relaxed);
+        if num > 0 {
+            let from_disk = self
+                .unacked
+                .drain(..num)
+                .filter(|target| *target == Target::Disk)
+                .count();
+            self.disk_acker.forward(from_disk);
+        }
+    }
+}
+
+impl Stream for Reader {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.ack_notifier.register(cx.waker());
+        self.forward_acks();
+
+        if !self.memory.is_terminated() {
+            if let Poll::Ready(Some(event)) = Pin::new(&mut self.memory).poll_next(cx) {
+                self.unacked.push_back(Target::Memory);
+                return Poll::Ready(Some(event));
+            }
+        }
+
+        match Pin::new(&mut self.disk).poll_next(cx) {
+            Poll::Ready(Some(event)) => {
+                self.unacked.push_back(Target::Disk);
+                Poll::Ready(Some(event))
+            }
+            Poll::Ready(None) if self.memory.is_terminated() => Poll::Ready(None),
+            _ => Poll::Pending,
+        }
+    }
+}
+
+impl Drop for Reader {
+    fn drop(&mut self) {
+        // Let the disk buffer delete whatever was acked before it shuts down.
+        self.forward_acks();
+    }
+}
@@ -0,0 +1,88 @@
+use futures::Stream;
+use pin_project::pin_project;
+use std::{
+    future::Future,
+    num::NonZeroU32,
+    pin::Pin,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+};
+use tokio::time::{sleep_until, Duration, Instant, Sleep};
+
+/// A rate budget shared between any number of buffer readers, capping their
+/// combined output to `events_per_second`.
+///
+/// Readers reserve send slots one at a time, in the order they ask for them,
+/// so no single reader can starve the others. The budget does not allow for
+/// bursts: slots are spaced evenly at `1 / events_per_second`.
+#[derive(Clone, Debug)]
+pub struct RateBudget {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    interval: Duration,
+    next_slot: Mutex<Instant>,
+}
+
+impl RateBudget {
+    pub fn new(events_per_second: NonZeroU32) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                interval: Duration::from_secs(1) / events_per_second.get(),
+                next_slot: Mutex::new(Instant::now()),
+            }),
+        }
+    }
+
+    /// Wrap `stream` so that each item it yields draws from this budget.
+    pub fn limit<S: Stream>(&self, stream: S) -> RateLimited<S> {
+        RateLimited {
+            inner: stream,
+            budget: self.clone(),
+            slot: None,
+        }
+    }
+
+    fn reserve(&self) -> Instant {
+        let mut next_slot = self.inner.next_slot.lock().unwrap();
+        let slot = std::cmp::max(*next_slot, Instant::now());
+        *next_slot = slot + self.inner.interval;
+        slot
+    }
+}
+
+#[pin_project]
+pub struct RateLimited<S> {
+    #[pin]
+    inner: S,
+    budget: RateBudget,
+    slot: Option<Pin<Box<Sleep>>>,
+}
+
+impl<S: Stream> Stream for RateLimited<S> {
+    type Item = S::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+
+        let budget = this.budget;
+        let slot = this
+            .slot
+            .get_or_insert_with(|| Box::pin(sleep_until(budget.reserve())));
+        if slot.as_mut().poll(cx).is_pending() {
+            return Poll::Pending;
+        }
+
+        match this.inner.poll_next(cx) {
+            Poll::Ready(item) => {
+                // The slot is spent once an item (or the end of the stream)
+                // is yielded, the next poll reserves a fresh one.
+                *this.slot = None;
+                Poll::Ready(item)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
@@ -0,0 +1,77 @@
+use crate::event::Event;
+use futures::Stream;
+use pin_project::pin_project;
+use std::{
+    collections::VecDeque,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+/// Wraps a buffer reader, keeping the last `capacity` events it yielded so
+/// they can be read again without going back to the buffer.
+#[pin_project]
+pub struct Recent<S> {
+    #[pin]
+    inner: S,
+    recent: VecDeque<Event>,
+    capacity: usize,
+}
+
+impl<S> Recent<S> {
+    pub fn new(inner: S, capacity: usize) -> Self {
+        Self {
+            inner,
+            recent: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// The last `n` events yielded, oldest first. Returns fewer events if
+    /// fewer than `n` have been yielded or `n` is larger than the capacity.
+    pub fn recent(&self, n: usize) -> Vec<Event> {
+        let skip = self.recent.len().saturating_sub(n);
+        self.recent.iter().skip(skip).cloned().collect()
+    }
+}
+
+impl<S: Stream<Item = Event>> Stream for Recent<S> {
+    type Item = Event;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        let event = futures::ready!(this.inner.poll_next(cx));
+        if let Some(event) = &event {
+            if *this.capacity > 0 {
+                if this.recent.len() == *this.capacity {
+                    this.recent.pop_front();
+                }
+                this.recent.push_back(event.clone());
+            }
+        }
+        Poll::Ready(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Recent;
+    use crate::event::Event;
+    use futures::{stream, StreamExt};
+
+    #[tokio::test]
+    async fn recent_returns_last_events_in_order() {
+        let events = (0..5)
+            .map(|i| Event::from(i.to_string()))
+            .collect::<Vec<_>>();
+        let mut reader = Recent::new(stream::iter(events.clone()), 4);
+
+        assert!(reader.recent(3).is_empty());
+        for _ in 0..5 {
+            reader.next().await.unwrap();
+        }
+
+        assert_eq!(reader.recent(3), events[2..]);
+        // Only the last four are kept.
+        assert_eq!(reader.recent(10), events[1..]);
+    }
+}
@@ -0,0 +1,147 @@
+use futures::{channel::mpsc, Sink, Stream};
+use pin_project::pin_project;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// Create an unbounded channel which sheds items once `soft_cap` of them are
+/// queued.
+///
+/// Sends never apply backpressure. The cap is "soft" in that concurrent
+/// senders may briefly overshoot it by the number of senders racing each
+/// other.
+pub fn channel<T>(soft_cap: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = mpsc::unbounded();
+    let queued = Arc::new(AtomicUsize::new(0));
+
+    let tx = Sender {
+        inner: tx,
+        queued: Arc::clone(&queued),
+        soft_cap,
+        dropped: Arc::new(AtomicU64::new(0)),
+    };
+    let rx = Receiver { inner: rx, queued };
+    (tx, rx)
+}
+
+pub struct Sender<T> {
+    inner: mpsc::UnboundedSender<T>,
+    queued: Arc<AtomicUsize>,
+    soft_cap: usize,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<T> Sender<T> {
+    /// The number of items shed above the cap, shared between all senders
+    /// of the channel.
+    pub fn dropped_events(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.dropped)
+    }
+}
+
+// Deriving `Clone` would needlessly require `T: Clone`.
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            queued: Arc::clone(&self.queued),
+            soft_cap: self.soft_cap,
+            dropped: Arc::clone(&self.dropped),
+        }
+    }
+}
+
+impl<T> Sink<T> for Sender<T> {
+    type Error = ();
+
+    fn poll_ready(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        if self.queued.load(Ordering::Relaxed) >= self.soft_cap {
+            debug!(
+                message = "Shedding load; dropping event.",
+                internal_log_rate_secs = 10
+            );
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+
+        self.queued.fetch_add(1, Ordering::Relaxed);
+        self.inner.unbounded_send(item).map_err(|error| {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            error!(message = "Sender error.", %error);
+        })
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[pin_project]
+pub struct Receiver<T> {
+    #[pin]
+    inner: mpsc::UnboundedReceiver<T>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl<T> Stream for Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.project();
+        match this.inner.poll_next(cx) {
+            Poll::Ready(Some(item)) => {
+                this.queued.fetch_sub(1, Ordering::Relaxed);
+                Poll::Ready(Some(item))
+            }
+            poll => poll,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::channel;
+    use futures::{future, Sink, Stream};
+    use std::{sync::atomic::Ordering, task::Poll};
+
+    #[tokio::test]
+    async fn sheds_above_soft_cap() {
+        future::lazy(|cx| {
+            let (tx, rx) = channel(2);
+            let dropped_events = tx.dropped_events();
+
+            let mut tx = Box::pin(tx);
+
+            for item in 1..=4 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(tx.as_mut().start_send(item), Ok(()));
+            }
+
+            let mut rx = Box::pin(rx);
+
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(1)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(2)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
+            assert_eq!(dropped_events.load(Ordering::Relaxed), 2);
+
+            // Draining frees up room under the cap again.
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            assert_eq!(tx.as_mut().start_send(5), Ok(()));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(5)));
+        })
+        .await;
+    }
+}
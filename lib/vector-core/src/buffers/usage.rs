@@ -0,0 +1,220 @@
+use super::BufferInputCloner;
+use crate::event::Event;
+use futures::{Sink, Stream};
+use std::{
+    convert::TryFrom,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicU64, AtomicUsize, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+};
+
+/// A cheap handle on how full a buffer is, for reporting.
+///
+/// Memory buffers report their events, disk buffers their bytes, and `0`
+/// for the other. The numbers are not updated atomically together, so they
+/// may briefly lag behind events being sent or read.
+#[derive(Clone, Debug)]
+pub struct BufferUsage {
+    inner: Usage,
+}
+
+#[derive(Clone, Debug)]
+enum Usage {
+    Memory {
+        sent: Arc<AtomicU64>,
+        dropped: Option<Arc<AtomicU64>>,
+        received: Arc<AtomicU64>,
+        max_events: usize,
+    },
+    Disk {
+        current_bytes: Arc<AtomicUsize>,
+        max_bytes: usize,
+    },
+}
+
+impl BufferUsage {
+    /// Track the events queued in a memory buffer holding up to
+    /// `max_events`, by counting what its inputs send, drop and its reader
+    /// reads.
+    pub fn track_memory(
+        tx: BufferInputCloner,
+        rx: Box<dyn Stream<Item = Event> + Send>,
+        max_events: usize,
+    ) -> (
+        BufferInputCloner,
+        Box<dyn Stream<Item = Event> + Send>,
+        Self,
+    ) {
+        let sent = Arc::new(AtomicU64::new(0));
+        let received = Arc::new(AtomicU64::new(0));
+        let usage = Self {
+            inner: Usage::Memory {
+                sent: Arc::clone(&sent),
+                dropped: tx.dropped_events(),
+                received: Arc::clone(&received),
+                max_events,
+            },
+        };
+
+        let tx = BufferInputCloner::Counted(Box::new(tx), sent);
+        let rx = Box::new(CountReceived {
+            inner: rx.into(),
+            received,
+        });
+        (tx, rx, usage)
+    }
+
+    /// Report `current_bytes` of a disk buffer holding up to `max_bytes`.
+    pub fn disk(current_bytes: Arc<AtomicUsize>, max_bytes: usize) -> Self {
+        Self {
+            inner: Usage::Disk {
+                current_bytes,
+                max_bytes,
+            },
+        }
+    }
+
+    /// The number of events queued in a memory buffer.
+    pub fn current_events(&self) -> usize {
+        match &self.inner {
+            Usage::Memory {
+                sent,
+                dropped,
+                received,
+                max_events,
+            } => {
+                // Read the counters in the reverse order of their updates,
+                // so none can be ahead of the one it is subtracted from.
+                let received = received.load(Ordering::Acquire);
+                let dropped = dropped
+                    .as_ref()
+                    .map_or(0, |dropped| dropped.load(Ordering::Acquire));
+                let sent = sent.load(Ordering::Acquire);
+                let current = sent.saturating_sub(dropped).saturating_sub(received);
+                // Each input of a bounded channel may hold one event beyond
+                // its capacity, which is not queued in the buffer. A
+                // rendezvous channel, with a capacity of 0, queues none.
+                usize::try_from(current).map_or(*max_events, |current| current.min(*max_events))
+            }
+            Usage::Disk { .. } => 0,
+        }
+    }
+
+    /// The number of events a memory buffer holds at most.
+    pub fn max_events(&self) -> usize {
+        match &self.inner {
+            Usage::Memory { max_events, .. } => *max_events,
+            Usage::Disk { .. } => 0,
+        }
+    }
+
+    /// The size of the events stored in a disk buffer.
+    pub fn current_bytes(&self) -> usize {
+        match &self.inner {
+            Usage::Memory { .. } => 0,
+            Usage::Disk { current_bytes, .. } => current_bytes.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The size a disk buffer holds at most.
+    pub fn max_bytes(&self) -> usize {
+        match &self.inner {
+            Usage::Memory { .. } => 0,
+            Usage::Disk { max_bytes, .. } => *max_bytes,
+        }
+    }
+}
+
+/// Counts the events sent to `inner`, including any it drops.
+pub(super) struct CountSent {
+    inner: Pin<Box<dyn Sink<Event, Error = ()> + Send>>,
+    sent: Arc<AtomicU64>,
+}
+
+impl CountSent {
+    pub(super) fn new(
+        inner: Box<dyn Sink<Event, Error = ()> + Send>,
+        sent: Arc<AtomicU64>,
+    ) -> Self {
+        Self {
+            inner: inner.into(),
+            sent,
+        }
+    }
+}
+
+impl Sink<Event> for CountSent {
+    type Error = ();
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.as_mut().poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: Event) -> Result<(), Self::Error> {
+        // Count before sending so the reader can never count it first.
+        self.sent.fetch_add(1, Ordering::AcqRel);
+        self.inner.as_mut().start_send(item).map_err(|()| {
+            self.sent.fetch_sub(1, Ordering::AcqRel);
+        })
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.as_mut().poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.as_mut().poll_close(cx)
+    }
+}
+
+struct CountReceived {
+    inner: Pin<Box<dyn Stream<Item = Event> + Send>>,
+    received: Arc<AtomicU64>,
+}
+
+impl Stream for CountReceived {
+    type Item = Event;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let event = futures::ready!(self.inner.as_mut().poll_next(cx));
+        if event.is_some() {
+            self.received.fetch_add(1, Ordering::AcqRel);
+        }
+        Poll::Ready(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::BufferUsage;
+    use crate::buffers::{BufferInputCloner, WhenFull};
+    use crate::event::Event;
+    use futures::{channel::mpsc, future, Sink, Stream};
+    use std::{pin::Pin, task::Poll};
+
+    #[tokio::test]
+    async fn tracks_memory_events() {
+        future::lazy(|cx| {
+            let (tx, rx) = mpsc::channel(2);
+            let tx = BufferInputCloner::Memory(tx, WhenFull::DropNewest, Default::default());
+            let (tx, rx, usage) = BufferUsage::track_memory(tx, Box::new(rx), 2);
+
+            let mut tx = Pin::from(tx.get());
+            let mut rx = Pin::from(rx);
+
+            for _ in 0..5 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                assert_eq!(tx.as_mut().start_send(Event::from("foo")), Ok(()));
+                assert!(usage.current_events() <= usage.max_events());
+            }
+            assert_eq!(usage.current_events(), 2);
+
+            while let Poll::Ready(Some(_)) = rx.as_mut().poll_next(cx) {}
+            assert_eq!(usage.current_events(), 0);
+        })
+        .await;
+    }
+}
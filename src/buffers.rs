@@ -1,11 +1,126 @@
 use crate::config::Resource;
 use crate::event::Event;
+#[cfg(feature = "disk-buffer")]
+use crate::internal_events::DiskBufferGrowing;
 use futures::channel::mpsc;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
+use std::fmt;
+#[cfg(feature = "disk-buffer")]
+use std::path::Path;
 use std::path::PathBuf;
+use std::time::Duration;
+#[cfg(feature = "disk-buffer")]
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+#[cfg(feature = "disk-buffer")]
+use tokio::runtime::Handle;
 pub use vector_core::buffers::*;
 
+/// How often disk buffers with a `min_free_space` check the free space in
+/// `data_dir`.
+#[cfg(feature = "disk-buffer")]
+const FREE_SPACE_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// How often disk buffers with a `growth_alert_secs` check their size.
+#[cfg(feature = "disk-buffer")]
+const GROWTH_CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// An error building a buffer from its [`BufferConfig`].
+#[derive(Debug)]
+pub enum BufferBuildError {
+    MissingDataDir,
+    InvalidMaxEvents,
+    InvalidMaxSize {
+        min: usize,
+    },
+    MissingOverflowMaxSize,
+    InvalidOverflowMaxSize {
+        min: usize,
+    },
+    /// The configured options can't be combined, or need a feature this
+    /// build of Vector lacks.
+    Unsupported(&'static str),
+    #[cfg(feature = "disk-buffer")]
+    InsufficientFreeSpace {
+        data_dir: PathBuf,
+        available: u64,
+        required: u64,
+    },
+    #[cfg(feature = "disk-buffer")]
+    DiskOpenFailed(disk::Error),
+    /// The option runs a task alongside the buffer, which needs the buffer to
+    /// be built within a Tokio runtime.
+    #[cfg(feature = "disk-buffer")]
+    MissingRuntime(&'static str),
+}
+
+impl fmt::Display for BufferBuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BufferBuildError::MissingDataDir => {
+                write!(f, "Must set data_dir to use on-disk buffering.")
+            }
+            BufferBuildError::InvalidMaxEvents => write!(f, "max_events must be greater than 0"),
+            BufferBuildError::InvalidMaxSize { min } => {
+                write!(f, "max_size must be at least {} bytes", min)
+            }
+            BufferBuildError::MissingOverflowMaxSize => write!(
+                f,
+                "Must set overflow_max_size to use when_full = \"overflow\"."
+            ),
+            BufferBuildError::InvalidOverflowMaxSize { min } => {
+                write!(f, "overflow_max_size must be at least {} bytes", min)
+            }
+            BufferBuildError::Unsupported(reason) => write!(f, "{}", reason),
+            #[cfg(feature = "disk-buffer")]
+            BufferBuildError::InsufficientFreeSpace {
+                data_dir,
+                available,
+                required,
+            } => write!(
+                f,
+                "Only {} bytes are free in data_dir {:?}, but the buffer may need up to {} more bytes.",
+                available, data_dir, required
+            ),
+            #[cfg(feature = "disk-buffer")]
+            BufferBuildError::DiskOpenFailed(error) => write!(f, "{}", error),
+            #[cfg(feature = "disk-buffer")]
+            BufferBuildError::MissingRuntime(option) => write!(
+                f,
+                "{} requires the buffer to be built within a Tokio runtime.",
+                option
+            ),
+        }
+    }
+}
+
+impl BufferBuildError {
+    /// Whether building the buffer again later may succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            #[cfg(feature = "disk-buffer")]
+            BufferBuildError::DiskOpenFailed(error) => error.is_transient(),
+            _ => false,
+        }
+    }
+}
+
+impl std::error::Error for BufferBuildError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            #[cfg(feature = "disk-buffer")]
+            BufferBuildError::DiskOpenFailed(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
@@ -15,20 +130,87 @@ pub enum BufferConfig {
         max_events: usize,
         #[serde(default)]
         when_full: WhenFull,
+        #[serde(default)]
+        channel_kind: ChannelKind,
+        #[serde(default)]
+        in_memory_compression: bool,
+        #[serde(default)]
+        overflow_max_size: Option<usize>,
+        #[serde(default)]
+        block_timeout_secs: Option<u64>,
+        #[serde(default)]
+        max_size: Option<usize>,
+        #[serde(default)]
+        ttl_from_field: Option<TtlFromField>,
     },
     #[cfg(feature = "disk-buffer")]
     Disk {
         max_size: usize,
         #[serde(default)]
         when_full: WhenFull,
+        #[serde(default)]
+        require_free_space: bool,
+        #[serde(default)]
+        reader_memory_budget: Option<usize>,
+        #[serde(default)]
+        min_free_space: Option<u64>,
+        #[serde(default)]
+        block_timeout_secs: Option<u64>,
+        #[serde(default)]
+        open_retry: Option<OpenRetry>,
+        #[serde(default)]
+        ack_policy: AckPolicy,
+        #[serde(default)]
+        growth_alert_secs: Option<u64>,
+        #[serde(default)]
+        ttl_from_field: Option<TtlFromField>,
     },
 }
 
+/// How to retry opening a disk buffer that failed to open for a reason that
+/// may pass, such as its `data_dir` not being mounted yet.
+#[cfg(feature = "disk-buffer")]
+#[derive(Deserialize, Serialize, Debug, Clone, Copy, PartialEq)]
+pub struct OpenRetry {
+    /// The number of attempts to make, including the first one.
+    pub attempts: usize,
+    /// How long to wait between attempts.
+    #[serde(default = "OpenRetry::backoff_secs")]
+    pub backoff_secs: u64,
+}
+
+#[cfg(feature = "disk-buffer")]
+impl OpenRetry {
+    #[inline]
+    const fn backoff_secs() -> u64 {
+        1
+    }
+}
+
+/// Drops events as they are read from the buffer once their own timestamp,
+/// rather than the time they were buffered, is older than `ttl_secs`.
+#[derive(Deserialize, Serialize, Debug, Clone, PartialEq)]
+pub struct TtlFromField {
+    /// The field holding the event's timestamp.
+    pub field: String,
+    /// How long after its timestamp an event expires.
+    pub ttl_secs: u64,
+    /// What to do with events without a valid timestamp in `field`.
+    #[serde(default)]
+    pub on_invalid: expiring::InvalidTimestamp,
+}
+
 impl Default for BufferConfig {
     fn default() -> Self {
         BufferConfig::Memory {
             max_events: BufferConfig::memory_max_events(),
             when_full: Default::default(),
+            channel_kind: Default::default(),
+            in_memory_compression: false,
+            overflow_max_size: None,
+            block_timeout_secs: None,
+            max_size: None,
+            ttl_from_field: None,
         }
     }
 }
@@ -39,7 +221,36 @@ impl BufferConfig {
         500
     }
 
-    #[cfg_attr(not(feature = "disk-buffer"), allow(unused))]
+    /// Check that the buffer's sizes leave room for events at all.
+    pub fn validate(&self) -> Result<(), BufferBuildError> {
+        match self {
+            BufferConfig::Memory { max_events: 0, .. } => Err(BufferBuildError::InvalidMaxEvents),
+            BufferConfig::Memory {
+                max_size: Some(0), ..
+            } => Err(BufferBuildError::InvalidMaxSize { min: 1 }),
+            #[cfg(feature = "disk-buffer")]
+            BufferConfig::Memory {
+                overflow_max_size: Some(overflow_max_size),
+                ..
+            } if *overflow_max_size < disk::min_max_size() => {
+                Err(BufferBuildError::InvalidOverflowMaxSize {
+                    min: disk::min_max_size(),
+                })
+            }
+            #[cfg(feature = "disk-buffer")]
+            BufferConfig::Disk { max_size, .. } if *max_size < disk::min_max_size() => {
+                Err(BufferBuildError::InvalidMaxSize {
+                    min: disk::min_max_size(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Build this buffer, making a single attempt to open a disk buffer.
+    ///
+    /// Use [`BufferConfig::build_async`] to retry as its `open_retry`
+    /// allows.
     pub fn build(
         &self,
         data_dir: &Option<PathBuf>,
@@ -50,51 +261,748 @@ impl BufferConfig {
             Box<dyn Stream<Item = Event> + Send>,
             Acker,
         ),
-        String,
+        BufferBuildError,
     > {
+        self.build_channel(data_dir, sink_name)
+            .map(|built| self.expire_events(built))
+    }
+
+    /// Build this buffer, retrying to open a disk buffer as its `open_retry`
+    /// allows without blocking the runtime in between.
+    pub async fn build_async(
+        &self,
+        data_dir: &Option<PathBuf>,
+        sink_name: &str,
+    ) -> Result<
+        (
+            BufferInputCloner,
+            Box<dyn Stream<Item = Event> + Send>,
+            Acker,
+        ),
+        BufferBuildError,
+    > {
+        self.open_attempts()
+            .run(|| self.build_channel(data_dir, sink_name))
+            .await
+            .map(|built| self.expire_events(built))
+    }
+
+    /// Make the buffer's reader drop the events that `ttl_from_field` says
+    /// have expired.
+    fn expire_events(
+        &self,
+        (tx, rx, acker): (
+            BufferInputCloner,
+            Box<dyn Stream<Item = Event> + Send>,
+            Acker,
+        ),
+    ) -> (
+        BufferInputCloner,
+        Box<dyn Stream<Item = Event> + Send>,
+        Acker,
+    ) {
+        let ttl_from_field = match self {
+            BufferConfig::Memory { ttl_from_field, .. } => ttl_from_field,
+            #[cfg(feature = "disk-buffer")]
+            BufferConfig::Disk { ttl_from_field, .. } => ttl_from_field,
+        };
+        match ttl_from_field {
+            Some(TtlFromField {
+                field,
+                ttl_secs,
+                on_invalid,
+            }) => {
+                let rx = expiring::Reader::new(
+                    rx,
+                    acker,
+                    field.clone(),
+                    Duration::from_secs(*ttl_secs),
+                    *on_invalid,
+                );
+                let acker = rx.acker();
+                (tx, Box::new(rx), acker)
+            }
+            None => (tx, rx, acker),
+        }
+    }
+
+    fn open_attempts(&self) -> OpenAttempts {
+        match self {
+            #[cfg(feature = "disk-buffer")]
+            BufferConfig::Disk {
+                open_retry:
+                    Some(OpenRetry {
+                        attempts,
+                        backoff_secs,
+                    }),
+                ..
+            } => OpenAttempts::new(*attempts, Duration::from_secs(*backoff_secs)),
+            _ => OpenAttempts::new(1, Duration::default()),
+        }
+    }
+
+    #[cfg_attr(not(feature = "disk-buffer"), allow(unused))]
+    fn build_channel(
+        &self,
+        data_dir: &Option<PathBuf>,
+        sink_name: &str,
+    ) -> Result<
+        (
+            BufferInputCloner,
+            Box<dyn Stream<Item = Event> + Send>,
+            Acker,
+        ),
+        BufferBuildError,
+    > {
+        self.validate()?;
+
         match &self {
             BufferConfig::Memory {
                 max_events,
                 when_full,
+                channel_kind,
+                in_memory_compression,
+                overflow_max_size,
+                block_timeout_secs,
+                max_size,
+                ttl_from_field: _,
             } => {
-                let (tx, rx) = mpsc::channel(*max_events);
-                let tx = BufferInputCloner::Memory(tx, *when_full);
-                let rx = Box::new(rx);
-                Ok((tx, rx, Acker::Null))
+                let capacity = match channel_kind {
+                    ChannelKind::Rendezvous => 0,
+                    _ => *max_events,
+                };
+                check_block_timeout(*when_full, *block_timeout_secs)?;
+                // A soft capped buffer never applies back pressure, so it
+                // drops new events whatever `when_full` says, including the
+                // default "block".
+                if block_timeout_secs.is_some()
+                    && channel_kind == &ChannelKind::UnboundedWithSoftCap
+                {
+                    return Err(BufferBuildError::Unsupported("block_timeout_secs is not supported for an unbounded_with_soft_cap memory buffer, which never applies back pressure."));
+                }
+                if max_size.is_some()
+                    && (channel_kind == &ChannelKind::UnboundedWithSoftCap
+                        || matches!(when_full, WhenFull::DropOldest | WhenFull::Overflow))
+                {
+                    return Err(BufferBuildError::Unsupported("max_size is only supported for bounded or rendezvous memory buffers with when_full = \"block\" or \"drop_newest\"."));
+                }
+                match when_full {
+                    WhenFull::DropOldest => {
+                        if channel_kind != &ChannelKind::Bounded || *in_memory_compression {
+                            return Err(BufferBuildError::Unsupported("when_full = \"drop_oldest\" is only supported for uncompressed bounded memory buffers."));
+                        }
+                        let (tx, rx) = drop_oldest::channel(*max_events);
+                        return Ok((BufferInputCloner::DropOldest(tx), Box::new(rx), Acker::Null));
+                    }
+                    WhenFull::Overflow => {
+                        if channel_kind == &ChannelKind::UnboundedWithSoftCap
+                            || *in_memory_compression
+                        {
+                            return Err(BufferBuildError::Unsupported("when_full = \"overflow\" is only supported for uncompressed bounded or rendezvous memory buffers."));
+                        }
+                        return build_overflow(data_dir, sink_name, capacity, *overflow_max_size);
+                    }
+                    WhenFull::Block | WhenFull::DropNewest => {}
+                }
+                let (tx, rx): (_, Box<dyn Stream<Item = Event> + Send>) = match (
+                    channel_kind,
+                    in_memory_compression,
+                ) {
+                    (ChannelKind::UnboundedWithSoftCap, false) => {
+                        let (tx, rx) = soft_cap::channel(*max_events);
+                        (BufferInputCloner::SoftCapped(tx), Box::new(rx))
+                    }
+                    (ChannelKind::UnboundedWithSoftCap, true) => {
+                        return Err(BufferBuildError::Unsupported("Compression is not supported for an unbounded_with_soft_cap memory buffer."));
+                    }
+                    (_, false) => match max_size {
+                        Some(max_size) => {
+                            let (tx, rx) = byte_capped::channel(capacity, *max_size);
+                            (
+                                BufferInputCloner::ByteCapped(tx, *when_full, Default::default()),
+                                Box::new(rx),
+                            )
+                        }
+                        None => {
+                            let (tx, rx) = mpsc::channel(capacity);
+                            (
+                                BufferInputCloner::Memory(tx, *when_full, Default::default()),
+                                Box::new(rx),
+                            )
+                        }
+                    },
+                    (_, true) => {
+                        let (tx, rx) = compressed::channel(capacity, *max_size);
+                        (
+                            BufferInputCloner::Compressed(tx, *when_full, Default::default()),
+                            Box::new(rx),
+                        )
+                    }
+                };
+                Ok((with_block_timeout(tx, *block_timeout_secs), rx, Acker::Null))
             }
 
             #[cfg(feature = "disk-buffer")]
             BufferConfig::Disk {
                 max_size,
                 when_full,
+                require_free_space,
+                reader_memory_budget,
+                min_free_space,
+                block_timeout_secs,
+                open_retry: _,
+                ack_policy,
+                growth_alert_secs,
+                ttl_from_field: _,
             } => {
-                let data_dir = data_dir
-                    .as_ref()
-                    .ok_or_else(|| "Must set data_dir to use on-disk buffering.".to_string())?;
+                check_block_timeout(*when_full, *block_timeout_secs)?;
+                match when_full {
+                    WhenFull::DropOldest => {
+                        return Err(BufferBuildError::Unsupported(
+                            "when_full = \"drop_oldest\" is only supported for memory buffers.",
+                        ));
+                    }
+                    WhenFull::Overflow => {
+                        return Err(BufferBuildError::Unsupported(
+                            "when_full = \"overflow\" is only supported for memory buffers.",
+                        ));
+                    }
+                    WhenFull::Block | WhenFull::DropNewest => {}
+                }
+                let data_dir = data_dir.as_ref().ok_or(BufferBuildError::MissingDataDir)?;
                 let buffer_dir = format!("{}_buffer", sink_name);
 
-                let (tx, rx, acker) = disk::open(&data_dir, buffer_dir.as_ref(), *max_size)
-                    .map_err(|error| error.to_string())?;
-                let tx = BufferInputCloner::Disk(tx, *when_full);
-                Ok((tx, rx, acker))
+                check_free_space(
+                    &data_dir,
+                    &data_dir.join(&buffer_dir),
+                    *max_size,
+                    *require_free_space,
+                )?;
+
+                // Check for the runtimes watching the buffer before opening it.
+                let free_space_runtime = match min_free_space {
+                    Some(_) => Some(current_runtime("min_free_space")?),
+                    None => None,
+                };
+                let growth_runtime = match growth_alert_secs {
+                    Some(_) => Some(current_runtime("growth_alert_secs")?),
+                    None => None,
+                };
+
+                let (tx, rx, acker) = disk::open(
+                    &data_dir,
+                    buffer_dir.as_ref(),
+                    *max_size,
+                    *reader_memory_budget,
+                )
+                .map_err(BufferBuildError::DiskOpenFailed)?;
+                if let (Some(min_free_space), Some(runtime)) = (min_free_space, free_space_runtime)
+                {
+                    watch_free_space(&runtime, data_dir.clone(), *min_free_space, tx.low_space());
+                }
+                if let (Some(growth_alert_secs), Some(runtime)) =
+                    (growth_alert_secs, growth_runtime)
+                {
+                    watch_growth(
+                        &runtime,
+                        sink_name.to_string(),
+                        &tx.current_size(),
+                        *max_size,
+                        Duration::from_secs(*growth_alert_secs),
+                    );
+                }
+                let (rx, acker) = match ack_policy {
+                    AckPolicy::OnConfirm => (rx, acker),
+                    AckPolicy::OnRead => (disk::ack_on_read(rx, acker), Acker::Null),
+                };
+                let tx = BufferInputCloner::Disk(Box::new(tx), *when_full, Default::default());
+                Ok((with_block_timeout(tx, *block_timeout_secs), rx, acker))
             }
         }
     }
 
+    /// Build this buffer with a reader that draws from `budget` before
+    /// yielding each event, capping the combined rate of every buffer
+    /// sharing it.
+    pub fn build_with_rate_budget(
+        &self,
+        data_dir: &Option<PathBuf>,
+        sink_name: &str,
+        budget: &RateBudget,
+    ) -> Result<
+        (
+            BufferInputCloner,
+            Box<dyn Stream<Item = Event> + Send>,
+            Acker,
+        ),
+        BufferBuildError,
+    > {
+        let (tx, rx, acker) = self.build(data_dir, sink_name)?;
+        Ok((tx, Box::new(budget.limit(rx)), acker))
+    }
+
+    /// Build this buffer along with a handle reporting how full it is.
+    pub fn build_with_metrics(
+        &self,
+        data_dir: &Option<PathBuf>,
+        sink_name: &str,
+    ) -> Result<
+        (
+            BufferInputCloner,
+            Box<dyn Stream<Item = Event> + Send>,
+            Acker,
+            BufferUsage,
+        ),
+        BufferBuildError,
+    > {
+        if let BufferConfig::Memory {
+            when_full: WhenFull::Overflow,
+            ..
+        } = self
+        {
+            return Err(BufferBuildError::Unsupported(
+                "Buffer usage is not reported for when_full = \"overflow\".",
+            ));
+        }
+
+        // Expired events are dropped after the usage counts them as read.
+        let (tx, rx, acker) = self.build_channel(data_dir, sink_name)?;
+        let ((tx, rx, acker), usage) = match self {
+            BufferConfig::Memory {
+                max_events,
+                channel_kind,
+                ..
+            } => {
+                let capacity = match channel_kind {
+                    ChannelKind::Rendezvous => 0,
+                    _ => *max_events,
+                };
+                let (tx, rx, usage) = BufferUsage::track_memory(tx, rx, capacity);
+                ((tx, rx, acker), usage)
+            }
+            #[cfg(feature = "disk-buffer")]
+            BufferConfig::Disk { max_size, .. } => {
+                let current_size = disk_writer(&tx)
+                    .expect("Disk buffers are written by a disk writer")
+                    .current_size();
+                let usage = BufferUsage::disk(current_size, *max_size);
+                ((tx, rx, acker), usage)
+            }
+        };
+        let (tx, rx, acker) = self.expire_events((tx, rx, acker));
+        Ok((tx, rx, acker, usage))
+    }
+
     /// Resources that the sink is using.
     #[cfg_attr(not(feature = "disk-buffer"), allow(unused))]
     pub fn resources(&self, sink_name: &str) -> Vec<Resource> {
         match self {
-            BufferConfig::Memory { .. } => Vec::new(),
             #[cfg(feature = "disk-buffer")]
-            BufferConfig::Disk { .. } => vec![Resource::DiskBuffer(sink_name.to_string())],
+            BufferConfig::Memory {
+                when_full: WhenFull::Overflow,
+                ..
+            }
+            | BufferConfig::Disk { .. } => vec![Resource::DiskBuffer(sink_name.to_string())],
+            BufferConfig::Memory { .. } => Vec::new(),
+        }
+    }
+}
+
+fn check_block_timeout(
+    when_full: WhenFull,
+    block_timeout_secs: Option<u64>,
+) -> Result<(), BufferBuildError> {
+    if block_timeout_secs.is_some() && when_full != WhenFull::Block {
+        return Err(BufferBuildError::Unsupported(
+            "block_timeout_secs is only supported with when_full = \"block\".",
+        ));
+    }
+    Ok(())
+}
+
+/// Make the buffer's inputs drop events once they have been blocked for
+/// `block_timeout_secs`.
+fn with_block_timeout(tx: BufferInputCloner, block_timeout_secs: Option<u64>) -> BufferInputCloner {
+    match block_timeout_secs {
+        Some(secs) => {
+            // Share the counter of the inputs within, so the events they drop
+            // themselves are still counted.
+            let dropped_events = tx.dropped_events().unwrap_or_default();
+            BufferInputCloner::BlockTimeout(Box::new(tx), Duration::from_secs(secs), dropped_events)
+        }
+        None => tx,
+    }
+}
+
+#[cfg(feature = "disk-buffer")]
+fn disk_writer(tx: &BufferInputCloner) -> Option<&disk::Writer> {
+    match tx {
+        BufferInputCloner::Disk(writer, _, _) => Some(writer.as_ref()),
+        BufferInputCloner::BlockTimeout(inner, _, _) => disk_writer(inner),
+        _ => None,
+    }
+}
+
+/// Build a memory buffer of `capacity` events which spills to a disk buffer of
+/// `overflow_max_size` bytes while it is full.
+#[cfg(feature = "disk-buffer")]
+fn build_overflow(
+    data_dir: &Option<PathBuf>,
+    sink_name: &str,
+    capacity: usize,
+    overflow_max_size: Option<usize>,
+) -> Result<
+    (
+        BufferInputCloner,
+        Box<dyn Stream<Item = Event> + Send>,
+        Acker,
+    ),
+    BufferBuildError,
+> {
+    let overflow_max_size = overflow_max_size.ok_or(BufferBuildError::MissingOverflowMaxSize)?;
+    let data_dir = data_dir.as_ref().ok_or(BufferBuildError::MissingDataDir)?;
+    let buffer_dir = format!("{}_buffer", sink_name);
+
+    let (disk_tx, disk_rx, disk_acker) =
+        disk::open(&data_dir, buffer_dir.as_ref(), overflow_max_size, None)
+            .map_err(BufferBuildError::DiskOpenFailed)?;
+    let (tx, rx) = mpsc::channel(capacity);
+    let rx = overflow::Reader::new(rx, disk_rx, disk_acker);
+    let acker = rx.acker();
+    Ok((
+        BufferInputCloner::Overflow(tx, Box::new(disk_tx)),
+        Box::new(rx),
+        acker,
+    ))
+}
+
+#[cfg(not(feature = "disk-buffer"))]
+fn build_overflow(
+    _data_dir: &Option<PathBuf>,
+    _sink_name: &str,
+    _capacity: usize,
+    _overflow_max_size: Option<usize>,
+) -> Result<
+    (
+        BufferInputCloner,
+        Box<dyn Stream<Item = Event> + Send>,
+        Acker,
+    ),
+    BufferBuildError,
+> {
+    Err(BufferBuildError::Unsupported(
+        "when_full = \"overflow\" requires Vector to be built with the disk-buffer feature.",
+    ))
+}
+
+/// Check that the filesystem holding `data_dir` has room for the buffer to
+/// grow to `max_size`, on top of what `buffer_dir` already occupies.
+#[cfg(feature = "disk-buffer")]
+fn check_free_space(
+    data_dir: &Path,
+    buffer_dir: &Path,
+    max_size: usize,
+    require_free_space: bool,
+) -> Result<(), BufferBuildError> {
+    let available = match available_space(data_dir) {
+        Some(available) => available,
+        // Not knowing is no reason to refuse, `disk::open` reports any
+        // actual problem with `data_dir`.
+        None => return Ok(()),
+    };
+
+    let used: u64 = std::fs::read_dir(buffer_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.and_then(|entry| entry.metadata()).ok())
+                .map(|metadata| metadata.len())
+                .sum()
+        })
+        .unwrap_or(0);
+    // Leave some headroom for leveldb's own files and compaction.
+    let margin = max_size / 10;
+    let required = (max_size as u64)
+        .saturating_add(margin as u64)
+        .saturating_sub(used);
+
+    if available >= required {
+        Ok(())
+    } else {
+        let error = BufferBuildError::InsufficientFreeSpace {
+            data_dir: data_dir.into(),
+            available,
+            required,
+        };
+        if require_free_space {
+            Err(error)
+        } else {
+            warn!("{}", error);
+            Ok(())
+        }
+    }
+}
+
+/// Tracks how long a disk buffer has been growing without ever shrinking, a
+/// sign that its sink is stuck.
+#[cfg(feature = "disk-buffer")]
+struct GrowthMonitor {
+    threshold: Duration,
+    last_size: usize,
+    last_checked: Instant,
+    growing_since: Option<Instant>,
+    alerted: bool,
+}
+
+#[cfg(feature = "disk-buffer")]
+impl GrowthMonitor {
+    fn new(threshold: Duration, size: usize, now: Instant) -> Self {
+        Self {
+            threshold,
+            last_size: size,
+            last_checked: now,
+            growing_since: None,
+            alerted: false,
         }
     }
+
+    /// Record the buffer's `size` at `now`. Returns for how long the buffer
+    /// has been growing the first time that reaches the threshold, and again
+    /// only after the buffer has shrunk in between.
+    fn check(&mut self, size: usize, now: Instant) -> Option<Duration> {
+        if size < self.last_size {
+            self.growing_since = None;
+            self.alerted = false;
+        } else if size > self.last_size && self.growing_since.is_none() {
+            self.growing_since = Some(self.last_checked);
+        }
+        self.last_size = size;
+        self.last_checked = now;
+
+        let growing_for = now - self.growing_since?;
+        if growing_for >= self.threshold && !self.alerted {
+            self.alerted = true;
+            Some(growing_for)
+        } else {
+            None
+        }
+    }
+}
+
+/// Alert once the disk buffer whose size is `current_size` has only grown
+/// for `threshold`, checking every [`GROWTH_CHECK_INTERVAL`] until the
+/// buffer is dropped.
+#[cfg(feature = "disk-buffer")]
+fn watch_growth(
+    runtime: &Handle,
+    sink_name: String,
+    current_size: &Arc<AtomicUsize>,
+    max_size: usize,
+    threshold: Duration,
+) {
+    let current_size = Arc::downgrade(current_size);
+    runtime.spawn(async move {
+        let mut interval = tokio::time::interval(GROWTH_CHECK_INTERVAL);
+        let mut monitor = match current_size.upgrade() {
+            Some(size) => {
+                GrowthMonitor::new(threshold, size.load(Ordering::Relaxed), Instant::now())
+            }
+            None => return,
+        };
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            let size = match current_size.upgrade() {
+                Some(size) => size.load(Ordering::Relaxed),
+                None => break,
+            };
+            if let Some(growing_for) = monitor.check(size, Instant::now()) {
+                emit!(DiskBufferGrowing {
+                    sink_name: &sink_name,
+                    growing_for,
+                    current_size: size,
+                    max_size,
+                });
+            }
+        }
+    });
+}
+
+/// The runtime to spawn the task that `option` runs alongside the buffer on.
+#[cfg(feature = "disk-buffer")]
+fn current_runtime(option: &'static str) -> Result<Handle, BufferBuildError> {
+    Handle::try_current().map_err(|_| BufferBuildError::MissingRuntime(option))
+}
+
+/// Counts the attempts at building a buffer, to retry transient failures to
+/// open a disk buffer.
+struct OpenAttempts {
+    attempts: usize,
+    backoff: Duration,
+    attempt: usize,
+}
+
+impl OpenAttempts {
+    fn new(attempts: usize, backoff: Duration) -> Self {
+        Self {
+            attempts,
+            backoff,
+            attempt: 1,
+        }
+    }
+
+    /// Call `open` until it succeeds or fails for good, sleeping between
+    /// attempts.
+    async fn run<T>(
+        mut self,
+        mut open: impl FnMut() -> Result<T, BufferBuildError>,
+    ) -> Result<T, BufferBuildError> {
+        loop {
+            let backoff = match open() {
+                Err(error) => match self.retry_after(&error) {
+                    Some(backoff) => backoff,
+                    None => return Err(error),
+                },
+                opened => return opened,
+            };
+            tokio::time::sleep(backoff).await;
+        }
+    }
+
+    /// How long to wait before attempting again after `error`, or `None` if
+    /// it is final.
+    fn retry_after(&mut self, error: &BufferBuildError) -> Option<Duration> {
+        if !error.is_transient() || self.attempt >= self.attempts {
+            return None;
+        }
+        warn!(
+            message = "Failed to open disk buffer, retrying.",
+            %error,
+            attempt = self.attempt,
+            backoff_secs = %self.backoff.as_secs(),
+        );
+        self.attempt += 1;
+        Some(self.backoff)
+    }
+}
+
+/// Refuse writes to a disk buffer while fewer than `min_free_space` bytes
+/// are free in `data_dir`, checking every [`FREE_SPACE_CHECK_INTERVAL`] until
+/// the buffer's writers are dropped.
+#[cfg(feature = "disk-buffer")]
+fn watch_free_space(
+    runtime: &Handle,
+    data_dir: PathBuf,
+    min_free_space: u64,
+    low_space: disk::LowSpace,
+) {
+    let check = move |was_low| {
+        let available = available_space(&data_dir)?;
+        let is_low = available < min_free_space;
+        if is_low && !was_low {
+            warn!(
+                message = "Disk is low on space; disk buffer is refusing events.",
+                %available,
+                %min_free_space,
+                ?data_dir,
+            );
+        } else if !is_low && was_low {
+            info!(
+                message = "Disk buffer is accepting events again.",
+                ?data_dir
+            );
+        }
+        Some(is_low)
+    };
+
+    // Check once up front so the buffer starts out refusing events when the
+    // disk is already low on space.
+    let mut is_low = check(false).unwrap_or(false);
+    if !low_space.set(is_low) {
+        return;
+    }
+
+    runtime.spawn(async move {
+        let mut interval = tokio::time::interval(FREE_SPACE_CHECK_INTERVAL);
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            // Not knowing how much space is free is no reason to refuse
+            // events, but also none to stop refusing them.
+            is_low = check(is_low).unwrap_or(is_low);
+            if !low_space.set(is_low) {
+                break;
+            }
+        }
+    });
+}
+
+/// Bytes available to unprivileged processes on the filesystem holding
+/// `path`.
+#[cfg(all(unix, feature = "disk-buffer"))]
+fn available_space(path: &Path) -> Option<u64> {
+    let stat = nix::sys::statvfs::statvfs(path).ok()?;
+    Some(u64::from(stat.blocks_available()) * u64::from(stat.fragment_size()))
+}
+
+#[cfg(all(not(unix), feature = "disk-buffer"))]
+fn available_space(_path: &Path) -> Option<u64> {
+    None
 }
 
 #[cfg(test)]
 mod test {
-    use crate::buffers::{BufferConfig, WhenFull};
+    #[cfg(feature = "disk-buffer")]
+    use crate::buffers::disk;
+    #[cfg(feature = "disk-buffer")]
+    use crate::buffers::{AckPolicy, Acker, GrowthMonitor, OpenAttempts, OpenRetry};
+    use crate::buffers::{BufferBuildError, BufferConfig, ChannelKind, RateBudget, WhenFull};
+    #[cfg(feature = "disk-buffer")]
+    use crate::config::Resource;
+    use crate::event::Event;
+    use futures::{future, Sink, SinkExt, Stream, StreamExt};
+    use std::{num::NonZeroU32, pin::Pin, task::Poll};
+    use tokio::time::{timeout_at, Duration, Instant};
+
+    /// Asserts that `result` failed with an error matching `pattern`, and
+    /// evaluates to that error.
+    macro_rules! assert_build_err {
+        ($result:expr, $pattern:pat) => {{
+            let error = $result.err().expect("the buffer should fail to build");
+            assert!(matches!(error, $pattern), "{}", error);
+            error
+        }};
+    }
+
+    /// A bounded memory buffer with its other options at their defaults.
+    fn memory_config(max_events: usize, when_full: WhenFull) -> BufferConfig {
+        BufferConfig::Memory {
+            max_events,
+            when_full,
+            channel_kind: ChannelKind::Bounded,
+            in_memory_compression: false,
+            overflow_max_size: None,
+            block_timeout_secs: None,
+            max_size: None,
+            ttl_from_field: None,
+        }
+    }
+
+    /// A disk buffer with its other options at their defaults.
+    #[cfg(feature = "disk-buffer")]
+    fn disk_config(max_size: usize, when_full: WhenFull) -> BufferConfig {
+        BufferConfig::Disk {
+            max_size,
+            when_full,
+            require_free_space: false,
+            reader_memory_budget: None,
+            min_free_space: None,
+            block_timeout_secs: None,
+            open_retry: None,
+            ack_policy: AckPolicy::OnConfirm,
+            growth_alert_secs: None,
+            ttl_from_field: None,
+        }
+    }
 
     #[test]
     fn config_default_values() {
@@ -110,6 +1018,12 @@ mod test {
             BufferConfig::Memory {
                 max_events: 500,
                 when_full: WhenFull::Block,
+                channel_kind: ChannelKind::Bounded,
+                in_memory_compression: false,
+                overflow_max_size: None,
+                block_timeout_secs: None,
+                max_size: None,
+                ttl_from_field: None,
             },
         );
 
@@ -118,10 +1032,7 @@ mod test {
           type = "memory"
           max_events = 100
           "#,
-            BufferConfig::Memory {
-                max_events: 100,
-                when_full: WhenFull::Block,
-            },
+            memory_config(100, WhenFull::Block),
         );
 
         check(
@@ -129,10 +1040,34 @@ mod test {
           type = "memory"
           when_full = "drop_newest"
           "#,
-            BufferConfig::Memory {
-                max_events: 500,
-                when_full: WhenFull::DropNewest,
-            },
+            memory_config(500, WhenFull::DropNewest),
+        );
+
+        let mut config = memory_config(500, WhenFull::Block);
+        if let BufferConfig::Memory { channel_kind, .. } = &mut config {
+            *channel_kind = ChannelKind::UnboundedWithSoftCap;
+        }
+        check(
+            r#"
+          type = "memory"
+          channel_kind = "unbounded_with_soft_cap"
+          "#,
+            config,
+        );
+
+        let mut config = memory_config(500, WhenFull::Block);
+        if let BufferConfig::Memory {
+            block_timeout_secs, ..
+        } = &mut config
+        {
+            *block_timeout_secs = Some(30);
+        }
+        check(
+            r#"
+          type = "memory"
+          block_timeout_secs = 30
+          "#,
+            config,
         );
 
         #[cfg(feature = "disk-buffer")]
@@ -144,7 +1079,745 @@ mod test {
             BufferConfig::Disk {
                 max_size: 1024,
                 when_full: WhenFull::Block,
+                require_free_space: false,
+                reader_memory_budget: None,
+                min_free_space: None,
+                block_timeout_secs: None,
+                open_retry: None,
+                ack_policy: AckPolicy::OnConfirm,
+                growth_alert_secs: None,
+                ttl_from_field: None,
             },
         );
     }
+
+    #[test]
+    fn block_timeout_requires_block() {
+        let config = |when_full| {
+            let mut config = memory_config(10, when_full);
+            if let BufferConfig::Memory {
+                block_timeout_secs, ..
+            } = &mut config
+            {
+                *block_timeout_secs = Some(30);
+            }
+            config
+        };
+
+        assert_build_err!(
+            config(WhenFull::DropNewest).build(&None, "foo"),
+            BufferBuildError::Unsupported(_)
+        );
+
+        let (tx, _, _) = config(WhenFull::Block).build(&None, "foo").unwrap();
+        assert!(tx.dropped_events().is_some());
+    }
+
+    #[test]
+    fn memory_zero_max_events() {
+        let error = assert_build_err!(
+            memory_config(0, WhenFull::Block).build(&None, "foo"),
+            BufferBuildError::InvalidMaxEvents
+        );
+        assert_eq!(error.to_string(), "max_events must be greater than 0");
+
+        assert!(memory_config(1, WhenFull::Block)
+            .build(&None, "foo")
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn memory_max_size_blocks_before_max_events() {
+        let mut config = memory_config(100, WhenFull::Block);
+        if let BufferConfig::Memory { max_size, .. } = &mut config {
+            *max_size = Some(10_000);
+        }
+        let (tx, rx, _acker) = config.build(&None, "foo").unwrap();
+
+        future::lazy(|cx| {
+            let mut tx = Pin::from(tx.get());
+            let mut rx = Pin::from(rx);
+
+            let mut sent = 0;
+            while tx.as_mut().poll_ready(cx) == Poll::Ready(Ok(())) {
+                tx.as_mut()
+                    .start_send(Event::from("x".repeat(4000)))
+                    .unwrap();
+                sent += 1;
+            }
+            assert_eq!(sent, 3);
+
+            assert!(matches!(rx.as_mut().poll_next(cx), Poll::Ready(Some(_))));
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn memory_usage_returns_to_zero() {
+        let (tx, rx, _acker, usage) = memory_config(2, WhenFull::DropNewest)
+            .build_with_metrics(&None, "foo")
+            .unwrap();
+        assert_eq!(usage.max_events(), 2);
+
+        future::lazy(|cx| {
+            let mut tx = Pin::from(tx.get());
+            let mut rx = Pin::from(rx);
+
+            for _ in 0..4 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                tx.as_mut().start_send(Event::from("foo")).unwrap();
+                assert!(usage.current_events() <= usage.max_events());
+            }
+            assert_eq!(usage.current_events(), 2);
+
+            while let Poll::Ready(Some(_)) = rx.as_mut().poll_next(cx) {}
+            assert_eq!(usage.current_events(), 0);
+        })
+        .await;
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disk_ack_on_read() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let mut config = disk_config(10_000, WhenFull::Block);
+        if let BufferConfig::Disk { ack_policy, .. } = &mut config {
+            *ack_policy = AckPolicy::OnRead;
+        }
+        let events = (0..3)
+            .map(|i| Event::from(format!("event {}", i)))
+            .collect::<Vec<_>>();
+
+        {
+            let (tx, rx, acker) = config.build(&Some(data_dir.clone()), "foo").unwrap();
+            // The sink has nothing to ack.
+            assert!(matches!(acker, Acker::Null));
+
+            let mut tx = Pin::from(tx.get());
+            for event in events.clone() {
+                tx.send(event).await.unwrap();
+            }
+
+            let mut rx = Pin::from(rx);
+            for event in &events[..2] {
+                assert_eq!(rx.next().await.as_ref(), Some(event));
+            }
+        }
+
+        // Events read before shutting down are not read again.
+        let (_tx, rx, _acker) = config.build(&Some(data_dir), "foo").unwrap();
+        let mut rx = Pin::from(rx);
+        assert_eq!(rx.next().await, Some(events[2].clone()));
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disk_ack_on_read_after_crash() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+        let crashed_dir = crate::test_util::temp_dir();
+        std::fs::create_dir_all(crashed_dir.join("foo_buffer")).unwrap();
+
+        let mut config = disk_config(10_000, WhenFull::Block);
+        if let BufferConfig::Disk { ack_policy, .. } = &mut config {
+            *ack_policy = AckPolicy::OnRead;
+        }
+        let events = (0..3)
+            .map(|i| Event::from(format!("event {}", i)))
+            .collect::<Vec<_>>();
+
+        let (tx, rx, _acker) = config.build(&Some(data_dir.clone()), "foo").unwrap();
+        let mut tx = Pin::from(tx.get());
+        for event in events.clone() {
+            tx.send(event).await.unwrap();
+        }
+        let mut rx = Pin::from(rx);
+        for event in &events[..2] {
+            assert_eq!(rx.next().await.as_ref(), Some(event));
+        }
+
+        // Crash: the reader never gets to delete what it acked on drop, and
+        // the buffer is left on disk as it is while still open.
+        std::mem::forget(rx);
+        for entry in std::fs::read_dir(data_dir.join("foo_buffer")).unwrap() {
+            let path = entry.unwrap().path();
+            std::fs::copy(
+                &path,
+                crashed_dir
+                    .join("foo_buffer")
+                    .join(path.file_name().unwrap()),
+            )
+            .unwrap();
+        }
+
+        let (_tx, rx, _acker) = config.build(&Some(crashed_dir), "foo").unwrap();
+        let mut rx = Pin::from(rx);
+        let mut next = rx.next().await;
+        // At most the last event read comes back.
+        if next.as_ref() == Some(&events[1]) {
+            next = rx.next().await;
+        }
+        assert_eq!(next, Some(events[2].clone()));
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn growth_monitor_alerts_at_threshold() {
+        let start = std::time::Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+        let mut monitor = GrowthMonitor::new(Duration::from_secs(30), 0, start);
+
+        // Growing steadily, with a pause in between.
+        for &(secs, size) in &[(5, 100), (10, 200), (15, 200), (20, 300), (25, 400)] {
+            assert_eq!(monitor.check(size, at(secs)), None);
+        }
+        assert_eq!(monitor.check(500, at(30)), Some(Duration::from_secs(30)));
+        // Only once per growth streak.
+        assert_eq!(monitor.check(600, at(35)), None);
+
+        // Shrinking starts over.
+        assert_eq!(monitor.check(100, at(40)), None);
+        for secs in (45..70).step_by(5) {
+            assert_eq!(monitor.check(100 + secs as usize, at(secs)), None);
+        }
+        assert_eq!(monitor.check(200, at(70)), Some(Duration::from_secs(30)));
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn open_retries_transient_failures() {
+        let error = BufferBuildError::DiskOpenFailed(disk::Error::DataDirNotFound {
+            data_dir: "foo".into(),
+        });
+        let mut attempts = OpenAttempts::new(3, Duration::from_secs(1));
+
+        assert_eq!(attempts.retry_after(&error), Some(Duration::from_secs(1)));
+        assert_eq!(attempts.retry_after(&error), Some(Duration::from_secs(1)));
+        assert_eq!(attempts.retry_after(&error), None);
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn open_fails_fast_on_permanent_failure() {
+        let error = BufferBuildError::DiskOpenFailed(disk::Error::DataDirNotWritable {
+            data_dir: "foo".into(),
+        });
+        let mut attempts = OpenAttempts::new(3, Duration::from_secs(1));
+
+        assert_eq!(attempts.retry_after(&error), None);
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test]
+    async fn build_async_waits_for_data_dir() {
+        tokio::time::pause();
+        let data_dir = crate::test_util::temp_dir();
+        let mut config = disk_config(disk::min_max_size(), WhenFull::Block);
+        if let BufferConfig::Disk { open_retry, .. } = &mut config {
+            *open_retry = Some(OpenRetry {
+                attempts: 3,
+                backoff_secs: 1,
+            });
+        }
+
+        // The data_dir shows up between the second and third attempts, and
+        // waiting for it must not block this single threaded runtime.
+        let start = Instant::now();
+        let mount = async {
+            tokio::time::sleep(Duration::from_millis(1500)).await;
+            std::fs::create_dir(&data_dir).unwrap();
+        };
+        let (built, ()) =
+            future::join(config.build_async(&Some(data_dir.clone()), "foo"), mount).await;
+        assert!(built.is_ok());
+        // Two backoffs, so the third attempt opened it.
+        assert_eq!(start.elapsed(), Duration::from_secs(2));
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test]
+    async fn open_attempts_run_until_open() {
+        tokio::time::pause();
+        let mut opens = 0;
+        let opened = OpenAttempts::new(3, Duration::from_secs(1))
+            .run(|| {
+                opens += 1;
+                if opens < 3 {
+                    Err(BufferBuildError::DiskOpenFailed(
+                        disk::Error::DataDirNotFound {
+                            data_dir: "foo".into(),
+                        },
+                    ))
+                } else {
+                    Ok(opens)
+                }
+            })
+            .await;
+        assert_eq!(opened.unwrap(), 3);
+        assert_eq!(opens, 3);
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn disk_max_size_below_minimum() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let min = disk::min_max_size();
+        for &max_size in &[0, min - 1] {
+            let error = assert_build_err!(
+                disk_config(max_size, WhenFull::Block).validate(),
+                BufferBuildError::InvalidMaxSize { .. }
+            );
+            assert_eq!(
+                error.to_string(),
+                format!("max_size must be at least {} bytes", min)
+            );
+        }
+
+        assert!(disk_config(min, WhenFull::Block)
+            .build(&Some(data_dir), "foo")
+            .is_ok());
+    }
+
+    fn channel_kind_config(channel_kind: ChannelKind, when_full: WhenFull) -> BufferConfig {
+        let mut config = memory_config(2, when_full);
+        if let BufferConfig::Memory {
+            channel_kind: kind, ..
+        } = &mut config
+        {
+            *kind = channel_kind;
+        }
+        config
+    }
+
+    /// Sends `count` events through a memory buffer of the given kind with
+    /// `max_events = 2` and returns how many of them were accepted before the
+    /// buffer applied backpressure, followed by how many the reader received.
+    async fn memory_channel_kind(
+        channel_kind: ChannelKind,
+        when_full: WhenFull,
+        count: usize,
+    ) -> (usize, usize) {
+        let (tx, rx, _acker) = channel_kind_config(channel_kind, when_full)
+            .build(&None, "foo")
+            .unwrap();
+
+        future::lazy(|cx| {
+            let mut tx = Pin::from(tx.get());
+            let mut rx = Pin::from(rx);
+
+            let mut accepted = 0;
+            for _ in 0..count {
+                if tx.as_mut().poll_ready(cx) != Poll::Ready(Ok(())) {
+                    break;
+                }
+                tx.as_mut().start_send(Event::from("foo")).unwrap();
+                accepted += 1;
+            }
+
+            let mut received = 0;
+            while let Poll::Ready(Some(_)) = rx.as_mut().poll_next(cx) {
+                received += 1;
+            }
+
+            (accepted, received)
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn memory_bounded_channel() {
+        // The input's own slot admits one event beyond `max_events`.
+        assert_eq!(
+            memory_channel_kind(ChannelKind::Bounded, WhenFull::Block, 5).await,
+            (3, 3)
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_rendezvous_channel() {
+        assert_eq!(
+            memory_channel_kind(ChannelKind::Rendezvous, WhenFull::Block, 5).await,
+            (1, 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_unbounded_with_soft_cap_channel() {
+        // Never applies backpressure, sheds everything above the soft cap.
+        assert_eq!(
+            memory_channel_kind(ChannelKind::UnboundedWithSoftCap, WhenFull::DropNewest, 5).await,
+            (5, 2)
+        );
+    }
+
+    #[tokio::test]
+    async fn memory_drops_expired_events() {
+        let config: BufferConfig = toml::from_str(
+            r#"
+            type = "memory"
+            ttl_from_field.field = "ts"
+            ttl_from_field.ttl_secs = 60
+            "#,
+        )
+        .unwrap();
+        let (tx, rx, _acker, usage) = config.build_with_metrics(&None, "foo").unwrap();
+
+        let mut expired = Event::from("expired");
+        expired
+            .as_mut_log()
+            .insert("ts", chrono::Utc::now() - chrono::Duration::hours(1));
+        let mut fresh = Event::from("fresh");
+        fresh.as_mut_log().insert("ts", chrono::Utc::now());
+
+        future::lazy(|cx| {
+            let mut tx = Pin::from(tx.get());
+            let mut rx = Pin::from(rx);
+
+            for event in vec![expired, fresh.clone()] {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                tx.as_mut().start_send(event).unwrap();
+            }
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Ready(Some(fresh)));
+            assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
+            assert_eq!(usage.current_events(), 0);
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn memory_unbounded_with_soft_cap_never_blocks() {
+        // The default when_full = "block" drops above the soft cap too.
+        assert_eq!(
+            memory_channel_kind(ChannelKind::UnboundedWithSoftCap, WhenFull::Block, 5).await,
+            (5, 2)
+        );
+
+        let mut config = channel_kind_config(ChannelKind::UnboundedWithSoftCap, WhenFull::Block);
+        if let BufferConfig::Memory {
+            block_timeout_secs, ..
+        } = &mut config
+        {
+            *block_timeout_secs = Some(30);
+        }
+        assert_build_err!(config.build(&None, "foo"), BufferBuildError::Unsupported(_));
+    }
+
+    #[tokio::test]
+    async fn memory_rendezvous_usage() {
+        let (tx, _rx, _acker, usage) =
+            channel_kind_config(ChannelKind::Rendezvous, WhenFull::Block)
+                .build_with_metrics(&None, "foo")
+                .unwrap();
+        assert_eq!(usage.max_events(), 0);
+
+        future::lazy(|cx| {
+            let mut tx = Pin::from(tx.get());
+            assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+            tx.as_mut().start_send(Event::from("foo")).unwrap();
+            // The event waits in the input for handoff, not in the buffer.
+            assert_eq!(usage.current_events(), 0);
+        })
+        .await;
+    }
+
+    async fn count_until(mut rx: Box<dyn Stream<Item = Event> + Send>, deadline: Instant) -> usize {
+        let mut count = 0;
+        while let Ok(Some(_)) = timeout_at(deadline, rx.next()).await {
+            count += 1;
+        }
+        count
+    }
+
+    #[tokio::test]
+    async fn rate_budget_shared_between_buffers() {
+        tokio::time::pause();
+
+        let budget = RateBudget::new(NonZeroU32::new(10).unwrap());
+        let config = memory_config(100, WhenFull::Block);
+
+        let mut inputs = Vec::new();
+        let mut readers = Vec::new();
+        for sink_name in &["foo", "bar"] {
+            let (tx, rx, _acker) = config
+                .build_with_rate_budget(&None, sink_name, &budget)
+                .unwrap();
+            let mut input = tx.get();
+            for _ in 0..50 {
+                input.send(Event::from("foo")).await.unwrap();
+            }
+            inputs.push((tx, input));
+            readers.push(rx);
+        }
+
+        // Two seconds at ten events per second, plus the slot that lands
+        // exactly on the deadline.
+        let deadline = Instant::now() + Duration::from_secs(2);
+        let counts =
+            future::join_all(readers.into_iter().map(|rx| count_until(rx, deadline))).await;
+
+        assert!(counts.iter().sum::<usize>() <= 21, "{:?}", counts);
+        assert!(counts.iter().all(|count| *count >= 9), "{:?}", counts);
+    }
+
+    /// Writes three 4KB events into a disk buffer with room for two of them,
+    /// then reports whether a fourth event would be admitted, how many
+    /// events made it to the reader and how many were counted as dropped.
+    #[cfg(feature = "disk-buffer")]
+    async fn disk_write_past_max_size(when_full: WhenFull) -> (bool, usize, u64) {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let (tx, rx, _acker) = disk_config(10_000, when_full)
+            .build(&Some(data_dir), "foo")
+            .unwrap();
+        let dropped_events = tx.dropped_events().unwrap();
+
+        future::lazy(|cx| {
+            let mut tx = Pin::from(tx.get());
+            let mut rx = Pin::from(rx);
+
+            for _ in 0..3 {
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                tx.as_mut()
+                    .start_send(Event::from("x".repeat(4000)))
+                    .unwrap();
+            }
+            let ready = tx.as_mut().poll_ready(cx) == Poll::Ready(Ok(()));
+
+            let mut received = 0;
+            while let Poll::Ready(Some(_)) = rx.as_mut().poll_next(cx) {
+                received += 1;
+            }
+
+            let dropped = dropped_events.load(std::sync::atomic::Ordering::Relaxed);
+            (ready, received, dropped)
+        })
+        .await
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disk_drops_event_that_does_not_fit() {
+        assert_eq!(
+            disk_write_past_max_size(WhenFull::DropNewest).await,
+            (true, 2, 1)
+        );
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disk_blocks_on_event_that_does_not_fit() {
+        assert_eq!(
+            disk_write_past_max_size(WhenFull::Block).await,
+            (false, 2, 0)
+        );
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disk_drops_event_larger_than_buffer_when_blocking() {
+        // Also with a block timeout, whose inputs must keep counting the
+        // events the writer drops.
+        for &timeout in &[None, Some(60)] {
+            let data_dir = crate::test_util::temp_dir();
+            std::fs::create_dir(&data_dir).unwrap();
+
+            // Writers keep a tenth of the 10KB for compaction, and an empty
+            // buffer only admits events up to two thirds of the rest.
+            let mut config = disk_config(10_000, WhenFull::Block);
+            if let BufferConfig::Disk {
+                block_timeout_secs, ..
+            } = &mut config
+            {
+                *block_timeout_secs = timeout;
+            }
+            let (tx, rx, _acker) = config.build(&Some(data_dir), "foo").unwrap();
+            let dropped_events = tx.dropped_events().unwrap();
+
+            future::lazy(|cx| {
+                let mut tx = Pin::from(tx.get());
+                let mut rx = Pin::from(rx);
+
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                tx.as_mut()
+                    .start_send(Event::from("x".repeat(7000)))
+                    .unwrap();
+                // The writer is not left waiting for room that never comes.
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                tx.as_mut().start_send(Event::from("small")).unwrap();
+                assert_eq!(tx.as_mut().poll_flush(cx), Poll::Ready(Ok(())));
+
+                assert_eq!(
+                    rx.as_mut().poll_next(cx),
+                    Poll::Ready(Some(Event::from("small")))
+                );
+                assert_eq!(dropped_events.load(std::sync::atomic::Ordering::Relaxed), 1);
+            })
+            .await;
+        }
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn disk_without_data_dir() {
+        let error = assert_build_err!(
+            disk_config(1024, WhenFull::Block).build(&None, "foo"),
+            BufferBuildError::MissingDataDir
+        );
+        assert_eq!(
+            error.to_string(),
+            "Must set data_dir to use on-disk buffering."
+        );
+    }
+
+    #[cfg(all(unix, feature = "disk-buffer"))]
+    #[test]
+    fn disk_max_size_beyond_free_space() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let config = |require| {
+            let mut config = disk_config(usize::MAX / 2, WhenFull::Block);
+            if let BufferConfig::Disk {
+                require_free_space, ..
+            } = &mut config
+            {
+                *require_free_space = require;
+            }
+            config
+        };
+
+        assert_build_err!(
+            config(true).build(&Some(data_dir.clone()), "foo"),
+            BufferBuildError::InsufficientFreeSpace { .. }
+        );
+
+        // Without `require_free_space` this only warns.
+        assert!(config(false).build(&Some(data_dir), "foo").is_ok());
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn disk_min_free_space_requires_runtime() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let mut config = disk_config(10_000, WhenFull::Block);
+        if let BufferConfig::Disk { min_free_space, .. } = &mut config {
+            *min_free_space = Some(1);
+        }
+        assert_build_err!(
+            config.build(&Some(data_dir), "foo"),
+            BufferBuildError::MissingRuntime("min_free_space")
+        );
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn disk_growth_alert_requires_runtime() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let mut config = disk_config(10_000, WhenFull::Block);
+        if let BufferConfig::Disk {
+            growth_alert_secs, ..
+        } = &mut config
+        {
+            *growth_alert_secs = Some(60);
+        }
+        assert_build_err!(
+            config.build(&Some(data_dir), "foo"),
+            BufferBuildError::MissingRuntime("growth_alert_secs")
+        );
+    }
+
+    #[cfg(all(unix, feature = "disk-buffer"))]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn disk_refuses_events_below_min_free_space() {
+        for &(when_full, ready) in &[(WhenFull::DropNewest, true), (WhenFull::Block, false)] {
+            let data_dir = crate::test_util::temp_dir();
+            std::fs::create_dir(&data_dir).unwrap();
+
+            // No filesystem has this much space free.
+            let mut config = disk_config(10_000, when_full);
+            if let BufferConfig::Disk { min_free_space, .. } = &mut config {
+                *min_free_space = Some(u64::MAX);
+            }
+            let (tx, rx, _acker) = config.build(&Some(data_dir), "foo").unwrap();
+
+            future::lazy(|cx| {
+                let mut tx = Pin::from(tx.get());
+                let mut rx = Pin::from(rx);
+
+                assert_eq!(tx.as_mut().poll_ready(cx), Poll::Ready(Ok(())));
+                tx.as_mut().start_send(Event::from("foo")).unwrap();
+                assert_eq!(
+                    tx.as_mut().poll_ready(cx) == Poll::Ready(Ok(())),
+                    ready,
+                    "{:?}",
+                    when_full
+                );
+                assert_eq!(rx.as_mut().poll_next(cx), Poll::Pending);
+            })
+            .await;
+        }
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    fn overflow_config(max_events: usize) -> BufferConfig {
+        let mut config = memory_config(max_events, WhenFull::Overflow);
+        if let BufferConfig::Memory {
+            overflow_max_size, ..
+        } = &mut config
+        {
+            *overflow_max_size = Some(1_000_000);
+        }
+        config
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[test]
+    fn overflow_uses_disk_buffer() {
+        let config = overflow_config(2);
+        assert_eq!(
+            config.resources("foo"),
+            vec![Resource::DiskBuffer("foo".to_string())]
+        );
+
+        assert_build_err!(config.build(&None, "foo"), BufferBuildError::MissingDataDir);
+    }
+
+    #[cfg(feature = "disk-buffer")]
+    #[tokio::test(flavor = "multi_thread")]
+    async fn overflow_spills_to_disk_and_drains_memory_first() {
+        let data_dir = crate::test_util::temp_dir();
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let (tx, mut rx, acker) = overflow_config(2).build(&Some(data_dir), "foo").unwrap();
+        let events = (0..7)
+            .map(|i| Event::from(i.to_string()))
+            .collect::<Vec<_>>();
+
+        // The input's own slot admits one event beyond `max_events`, the
+        // rest spill to disk.
+        let mut input = tx.get();
+        for event in &events[..6] {
+            input.send(event.clone()).await.unwrap();
+        }
+
+        // Once the channel has room again, new events go to memory and are
+        // read ahead of those already spilled.
+        assert_eq!(rx.next().await.unwrap(), events[0]);
+        input.send(events[6].clone()).await.unwrap();
+
+        for &i in &[1, 2, 6, 3, 4, 5] {
+            assert_eq!(rx.next().await.unwrap(), events[i]);
+        }
+        acker.ack(7);
+    }
 }
@@ -0,0 +1,27 @@
+use super::InternalEvent;
+use metrics::counter;
+use std::time::Duration;
+
+#[derive(Debug)]
+pub struct DiskBufferGrowing<'a> {
+    pub sink_name: &'a str,
+    pub growing_for: Duration,
+    pub current_size: usize,
+    pub max_size: usize,
+}
+
+impl<'a> InternalEvent for DiskBufferGrowing<'a> {
+    fn emit_logs(&self) {
+        warn!(
+            message = "Disk buffer has only been growing; its sink may be stuck.",
+            sink = %self.sink_name,
+            growing_for_secs = %self.growing_for.as_secs(),
+            current_size = %self.current_size,
+            max_size = %self.max_size,
+        );
+    }
+
+    fn emit_metrics(&self) {
+        counter!("disk_buffer_growth_alerts_total", 1);
+    }
+}
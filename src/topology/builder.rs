@@ -120,7 +120,11 @@ pub async fn build_pieces(
         };
 
         let (input_tx, input_rx) = futures::channel::mpsc::channel(100);
-        let input_tx = buffers::BufferInputCloner::Memory(input_tx, buffers::WhenFull::Block);
+        let input_tx = buffers::BufferInputCloner::Memory(
+            input_tx,
+            buffers::WhenFull::Block,
+            Default::default(),
+        );
         let input_rx = crate::utilization::wrap(input_rx);
 
         let (output, control) = Fanout::new();
@@ -177,7 +181,10 @@ pub async fn build_pieces(
         let (tx, rx, acker) = if let Some(buffer) = buffers.remove(name) {
             buffer
         } else {
-            let buffer = sink.buffer.build(&config.global.data_dir, &name);
+            let buffer = sink
+                .buffer
+                .build_async(&config.global.data_dir, &name)
+                .await;
             match buffer {
                 Err(error) => {
                     errors.push(format!("Sink \"{}\": {}", name, error));
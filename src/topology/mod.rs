@@ -1097,6 +1097,14 @@ mod reload_tests {
         old_config.sinks["out"].buffer = BufferConfig::Disk {
             max_size: 1024,
             when_full: WhenFull::Block,
+            require_free_space: false,
+            reader_memory_budget: None,
+            min_free_space: None,
+            block_timeout_secs: None,
+            open_retry: None,
+            ack_policy: Default::default(),
+            growth_alert_secs: None,
+            ttl_from_field: None,
         };
 
         let mut new_config = old_config.clone();
@@ -1108,6 +1116,14 @@ mod reload_tests {
         new_config.sinks["out"].buffer = BufferConfig::Disk {
             max_size: 2048,
             when_full: WhenFull::Block,
+            require_free_space: false,
+            reader_memory_budget: None,
+            min_free_space: None,
+            block_timeout_secs: None,
+            open_retry: None,
+            ack_policy: Default::default(),
+            growth_alert_secs: None,
+            ttl_from_field: None,
         };
 
         reload_sink_test(
@@ -52,6 +52,14 @@ fn test_buffering() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            require_free_space: false,
+            reader_memory_budget: None,
+            min_free_space: None,
+            block_timeout_secs: None,
+            open_retry: None,
+            ack_policy: Default::default(),
+            growth_alert_secs: None,
+            ttl_from_field: None,
         };
         config.global.data_dir = Some(data_dir.clone());
         config.build().unwrap()
@@ -101,6 +109,14 @@ fn test_buffering() {
         config.sinks["out"].buffer = BufferConfig::Disk {
             max_size,
             when_full: Default::default(),
+            require_free_space: false,
+            reader_memory_budget: None,
+            min_free_space: None,
+            block_timeout_secs: None,
+            open_retry: None,
+            ack_policy: Default::default(),
+            growth_alert_secs: None,
+            ttl_from_field: None,
         };
         config.global.data_dir = Some(data_dir);
         config.build().unwrap()